@@ -25,7 +25,7 @@ def example_using_anthropic():
     content = Content.from_text("Hello, Claude!")
     content2 = Content.from_text("What is color of this image?")
     # for Claude, you need to construct a document content with llm parameter, and give the llm model name
-    content3 = Content.from_document("examples/python/white.jpg", llm="claude-3-5-haiku-latest")
+    content3 = Content.from_document("examples/python/white.jpg")
 
     # construct a message
     message = Message(content=[content, content2, content3])
@@ -56,7 +56,6 @@ def example_using_anthropic():
 
 
 def example_using_openai():
-    import os
     from goldenai import Content, Message, OpenAIRequest, send, count_tokens, LLMResponse
 
     content = Content.from_text("Hello, OpenAI!")
@@ -68,8 +67,6 @@ def example_using_openai():
     request = OpenAIRequest(model="gpt-4.1-nano", messages=[message],
                             # no max_tokens option for OpenAI
                             prompt="Please answer in Chinese",  # optional
-                            endpoint=f"{os.getenv('AZURE_OPENAI_ENDPOINT')}/openai/responses?api-version=2025-04-01-preview"
-                            # optional, here is an example using model provided by Azure, NOTE: only support 'responses' endpoint
                             )
 
     res = send(request)
@@ -86,9 +83,78 @@ def example_using_openai():
     print(res2)
 
 
-def example_using_openai_with_model():
+def example_errors_openai():
+    import time
+    from goldenai import Content, Message, OpenAIRequest, send, RateLimitError, GoldenAIError, RetryPolicy, \
+        set_retry_policy
+
+    # 429, 529 and other temporary errors are retried up to 3 times by default, with exponential backoff
+    set_retry_policy(RetryPolicy(max_attempts=5, max_retry_after=30))
+    request = OpenAIRequest(model="gpt-4.1-nano", messages=[Message(content=[Content.from_text("Hello!")])])
+    try:
+        print(send(request))
+    except RateLimitError as e:
+        # raised once the retries are used up, every error of goldenai carries the answer of the provider
+        print(e.status_code, e.request_id, e.body)
+        time.sleep(e.retry_after or 1)
+    except GoldenAIError as e:
+        print(type(e).__name__, e.provider, e)
+
+
+def example_http_settings_anthropic():
     import os
-    from goldenai import Content, Message, OpenAIRequest, send_with_model, LLMResponse
+    from goldenai import Content, Message, AnthropicRequest, send, HttpSettings, set_http_settings
+
+    # every request goes through the corporate proxy and trusts the internal CA
+    set_http_settings(HttpSettings(
+        proxy=os.getenv("HTTPS_PROXY", "http://proxy.internal:3128"),
+        no_proxy=["localhost", ".internal"],
+        ca_certificates=["internal-ca.pem"],
+    ))
+    request = AnthropicRequest(model="claude-3-5-haiku-latest", messages=[Message(content=[Content.from_text("Hello!")])])
+    # a single request with a shorter timeout
+    print(send(request, http=HttpSettings(connect_timeout=5, timeout=30)))
+    set_http_settings(None)
+
+
+def example_client_anthropic():
+    from goldenai import Content, Message, AnthropicRequest, GoldenAIClient, ProviderSettings, HttpSettings
+
+    # one client per customer, instead of the API keys in the environment
+    clients = {
+        "customer-a": GoldenAIClient(anthropic=ProviderSettings(api_key="sk-ant-...a")),
+        "customer-b": GoldenAIClient(
+            anthropic=ProviderSettings(api_key="sk-ant-...b", base_url="https://llm-gateway.internal/anthropic/v1",
+                                       headers={"x-tenant": "customer-b"}),
+            http=HttpSettings(ca_certificates=["internal-ca.pem"]),
+        ),
+    }
+    request = AnthropicRequest(model="claude-3-5-haiku-latest", messages=[Message(content=[Content.from_text("Hello!")])])
+    for customer, client in clients.items():
+        print(customer, client.count_tokens(request), client.send(request))
+
+
+def example_azure_openai():
+    import os
+    from goldenai import Content, Message, OpenAIRequest, send, count_tokens, AzureSettings, set_azure_settings
+
+    # every OpenAIRequest goes to the deployments of the Azure resource, with the api-key header
+    set_azure_settings(AzureSettings(
+        endpoint=os.getenv("AZURE_OPENAI_ENDPOINT"),  # e.g. "https://my-resource.openai.azure.com"
+        api_version="2025-04-01-preview",
+        deployments={"prod-gpt-41-nano": "gpt-4.1-nano"},  # deployment name: model name
+        api_key=os.getenv("AZURE_OPENAI_API_KEY"),
+        # or Microsoft Entra ID instead of the key:
+        # ad_token_provider=get_bearer_token_provider(DefaultAzureCredential(), "https://cognitiveservices.azure.com/.default"),
+    ))
+    request = OpenAIRequest(model="gpt-4.1-nano", messages=[Message(content=[Content.from_text("Hello!")])])
+    print(count_tokens(request))
+    print(send(request))  # sent to the deployment "prod-gpt-41-nano"
+    set_azure_settings(None)
+
+
+def example_using_openai_with_model():
+    from goldenai import Content, Message, OpenAIRequest, send, LLMResponse
     from pydantic import BaseModel
 
     class Test(BaseModel):
@@ -102,34 +168,188 @@ def example_using_openai_with_model():
     request = OpenAIRequest(model="gpt-5-nano", messages=[message],
                             # no max_tokens option for OpenAI
                             prompt="Please answer in Chinese",  # optional
-                            # endpoint=f"{os.getenv('AZURE_OPENAI_ENDPOINT')}/openai/responses?api-version=2025-04-01-preview"
-                            # optional, here is an example using model provided by Azure, NOTE: only support 'responses' endpoint
+                            reasoning_effort="minimal",  # optional, "low" by default for reasoning models
                             )
 
-    res = send_with_model(request, Test)
+    res = send(request, schema=Test,  # a JSON Schema as dict works as well
+               max_reasks=2  # optional, ask again with the validation errors if the output does not match
+               )
 
-    print(res)
+    print(Test.model_validate(res.parsed))
     print(res.cost())
 
-    # if you use send_with_model method to obtain a response, please make sure you will append
-    # the response using add_response_from_str method
-    request.add_response_from_str(str(res))
+    request.add_response(res)
     content2 = Content.from_text("Please answer again in English")
     message2 = Message(content=[content2])
     request.add_message(message2)
-    res2: LLMResponse = send_with_model(request_body=request, model=Test)
-    print(res2)
+    res2: LLMResponse = send(request_body=request, schema=Test)
+    print(res2.parsed)
+
+
+def example_stream_anthropic():
+    from goldenai import Content, Message, AnthropicRequest, stream
+
+    message = Message(content=[Content.from_text("Tell me a short story about a robot")])
+    request = AnthropicRequest(model="claude-3-5-haiku-latest", messages=[message])
+
+    # the response is streamed as events: "text_delta", "usage" and "stop"
+    events = stream(request)  # for OllamaRequest, use stream(request, chat=True) to stream in chat mode
+    for event in events:
+        if event.event_type == "text_delta":
+            print(event.text, end="", flush=True)
+    print()
+
+    # once the stream is exhausted, the aggregated response can be used like the one from send
+    res = events.response
+    print(res.cost())
+    request.add_response(res)
+
+
+def example_thinking_anthropic():
+    from goldenai import Content, Message, AnthropicRequest, send
+
+    message = Message(content=[Content.from_text("How many r are in strawberry?")])
+    request = AnthropicRequest(model="claude-sonnet-4-5", messages=[message], max_tokens=4096,
+                               thinking_budget=2048  # at least 1024 and less than max_tokens
+                               )
+    res = send(request)
+    for block in res.thinking:
+        print(block.thinking)
+    print(res)
+
+    # the thinking blocks are kept with their signatures, as required to continue the conversation
+    request.add_response(res)
+    request.add_message(Message(content=[Content.from_text("And in raspberry?")]))
+    print(send(request))
+
+
+def example_prompt_caching_anthropic():
+    from goldenai import Content, Message, AnthropicRequest, send
+
+    # the document is cached up to and including the marked block, "5m" or "1h"
+    document = Content.from_document("examples/python/test.pdf", cache_control="5m")
+    message = Message(content=[document, Content.from_text("What does this document say?")])
+    request = AnthropicRequest(model="claude-haiku-4-5", messages=[message],
+                               prompt="Please answer in Chinese",
+                               prompt_cache_control="5m"  # optional, caches the tools and the prompt
+                               )
+    res = send(request)
+    # the first request writes to the cache, the following ones read from it at a discount
+    print(res.usage.cache_creation_input_tokens, res.usage.cache_read_input_tokens)
+    print(res.cost())
+
+
+def example_async_openai():
+    import asyncio
+    from goldenai import Content, Message, OpenAIRequest, send_async
+
+    async def ask(question: str) -> str:
+        request = OpenAIRequest(model="gpt-4.1-nano", messages=[Message(content=[Content.from_text(question)])])
+        # the request runs on the shared runtime of goldenai, the event loop stays free in the meantime
+        return str(await send_async(request))
+
+    async def main():
+        answers = await asyncio.gather(ask("What is the capital of Germany?"), ask("What is the capital of France?"))
+        print(answers)
+
+    asyncio.run(main())
+
+
+def example_tools_openai():
+    from goldenai import Content, Message, OpenAIRequest, Tool, send
+
+    weather = Tool(name="get_weather", description="Get the current weather of a city",
+                   parameters={"type": "object", "properties": {"city": {"type": "string"}}, "required": ["city"]})
+    request = OpenAIRequest(model="gpt-4.1-nano", messages=[Message(content=[Content.from_text("How is the weather in Berlin?")])],
+                            tools=[weather],
+                            tool_choice="auto"  # optional: "auto", "none", "required" or the name of a tool
+                            )
+    res = send(request)
+
+    # the tool calls must be kept in the conversation, add_response takes care of it
+    request.add_response(res)
+    results = [Content.from_tool_result(call, f"Sunny in {call.arguments['city']}") for call in res.tool_calls]
+    request.add_message(Message(content=results))
+    print(send(request))
+
+
+def example_agent_anthropic():
+    from goldenai import AnthropicRequest, Content, Message, Tool, run_agent
+
+    def get_weather(city: str) -> str:
+        return f"Sunny in {city}"
+
+    weather = Tool(name="get_weather", description="Get the current weather of a city",
+                   parameters={"type": "object", "properties": {"city": {"type": "string"}}, "required": ["city"]})
+    request = AnthropicRequest(model="claude-3-5-haiku-latest",
+                               messages=[Message(content=[Content.from_text("How is the weather in Berlin and Paris?")])],
+                               tools=[weather])
+    res = run_agent(request, tools={"get_weather": get_weather}, max_steps=5)
+    print(res.stop_reason, len(res.steps), res.cost())
+    print(res)
+
+
+def example_using_gemini():
+    from goldenai import Content, Message, GeminiRequest, send, count_tokens
+
+    content = Content.from_text("What does this document say?")
+    content2 = Content.from_document("examples/python/test.pdf")
+    message = Message(content=[content, content2])
+    request = GeminiRequest(model="gemini-2.5-flash", messages=[message],
+                            prompt="Please answer in Chinese",  # optional
+                            max_output_tokens=1024  # optional
+                            )
+
+    print(count_tokens(request))
+    res = send(request)
+    print(res)
+    print(res.cost())
+
+
+def example_using_mistral():
+    from goldenai import Content, Message, MistralRequest, send
+
+    content = Content.from_text("What does this document say?")
+    # PDFs are read by mistral-ocr-latest and sent as markdown
+    content2 = Content.from_document("examples/python/test.pdf")
+    message = Message(content=[content, content2])
+    request = MistralRequest(model="mistral-small-latest", messages=[message],
+                             prompt="Please answer in Chinese",  # optional
+                             max_tokens=1024  # optional
+                             )
+
+    # there is no tokenizer of Mistral to count with, the usage of the response has the tokens
+    res = send(request)
+    print(res)
+    print(res.usage, res.cost())
+
+
+def example_using_openai_compat():
+    from goldenai import Content, Message, OpenAICompatRequest, send
+
+    content = Content.from_text("What is the color of this image?")
+    content2 = Content.from_document("examples/python/white.jpg")
+    message = Message(content=[content, content2])
+    # e.g. vLLM started with `vllm serve Qwen/Qwen2.5-VL-7B-Instruct`
+    request = OpenAICompatRequest(base_url="http://localhost:8000/v1", model="Qwen/Qwen2.5-VL-7B-Instruct",
+                                  messages=[message],
+                                  api_key="token-abc123",  # optional
+                                  )
+
+    res = send(request)
+    print(res)
 
 
 def example_using_ollama():
-    from goldenai import Content, Message, OllamaRequest, send
+    from goldenai import Content, Message, OllamaRequest, GenerationConfig, send
 
     content = Content.from_text("What is the color of this image?")
-    # DO NOT CONSTRUCT DOCUMENT CONTENT IN OLLAMA, PASS IT DIRECTLY IN REQUEST
-    # content = Content.from_document("white.jpg")  # WRONG USAGE! THIS WON'T WORK!
-    message = Message(content=[content])
+    content2 = Content.from_document("examples/python/white.jpg")  # sent as one of the images
+    message = Message(content=[content, content2])
     request = OllamaRequest(url="http://10.8.0.1:11434", model="qwen2.5vl:latest", messages=[message],
-                            prompt="Please answer in Chinese", image="examples/python/white.jpg")
+                            prompt="Please answer in Chinese",
+                            # optional, accepted by every request type
+                            generation_config=GenerationConfig(temperature=0.2, num_ctx=8192))
 
     res = send(request)
 
@@ -144,17 +364,33 @@ def example_chat_ollama():
     request = OllamaRequest(url="http://10.8.0.1:11434", model="qwen2.5vl:latest", messages=[message])
     res: LLMResponse = send(request)  # the first request can be sent either in send mode or chat mode
     print(res)
-    request.add_response(res)
+    request.add_response(res)  # keeps the context of send mode as well
     content2 = Content.from_text("Please answer again in Chinese")
     message2 = Message(content=[content2])
-    request.add_message(message2)  # MUST USE add_message to append messages in chat mode
-    res2: LLMResponse = chat(request)  # MUST USE chat to enable chat mode and understand the context
+    request.add_message(message2)
+    res2: LLMResponse = chat(request)  # send works as well, both modes understand the context
     print(res2)
+    # the usage of a conversation can be summed, Ollama also reports durations in seconds
+    usage = sum([res.usage, res2.usage])
+    print(usage.total_tokens, usage.durations)
 
 
 if __name__ == "__main__":
     example_using_anthropic()
     example_using_openai()
+    example_errors_openai()
+    example_http_settings_anthropic()
+    example_client_anthropic()
+    example_azure_openai()
     example_using_openai_with_model()
+    example_stream_anthropic()
+    example_thinking_anthropic()
+    example_prompt_caching_anthropic()
+    example_async_openai()
+    example_tools_openai()
+    example_agent_anthropic()
+    example_using_gemini()
+    example_using_mistral()
+    # example_using_openai_compat()
     # example_using_ollama()
     # example_chat_ollama()
@@ -1,2 +1 @@
-from goldenai.py.goldenai_py import send_with_model
 from .goldenai import *
@@ -0,0 +1,353 @@
+use anyhow::Result;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+use pythonize::{depythonize, pythonize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::error::to_py_err;
+use crate::http::{self, HttpSettings};
+use crate::message::{Content, ContentTypeInner, Message, ToolResult};
+use crate::request::ProviderRequest;
+use crate::response::{LLMResponse, Usage};
+use crate::tools::ToolCall;
+
+/// One round trip of the agent: the response of the model and the results of the tools it called
+#[derive(Clone, Debug)]
+#[pyclass(dict, get_all, frozen)]
+pub struct AgentStep {
+    pub response: LLMResponse,
+    pub tool_results: Vec<ToolResult>,
+}
+
+#[pymethods]
+impl AgentStep {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "AgentStep<tool_calls={},tool_results={}>",
+            self.response.tool_calls.len(),
+            self.tool_results.len()
+        ))
+    }
+}
+
+/// The outcome of `run_agent`, the final response and the trace of every step
+#[derive(Clone, Debug)]
+#[pyclass(dict, get_all, frozen)]
+pub struct AgentResult {
+    pub response: LLMResponse,
+    pub steps: Vec<AgentStep>,
+    pub stop_reason: String, // "completed", "max_steps" or "max_cost"
+    pub usage: Usage,        // summed over all steps
+}
+
+#[pymethods]
+impl AgentResult {
+    /// The cost of all steps
+    pub fn cost(&self) -> PyResult<f64> {
+        self.steps.iter().map(|step| step.response.cost()).sum()
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "AgentResult<steps={},stop_reason={:?}>",
+            self.steps.len(),
+            self.stop_reason
+        ))
+    }
+
+    fn __str__(&self) -> PyResult<String> {
+        Ok(self.response.to_string())
+    }
+}
+
+/// Call the Python function of a tool with the arguments chosen by the model
+///
+/// Failures are reported back to the model as an error result instead of ending the run, so it
+/// has the chance to correct its arguments
+fn call_tool(py: Python<'_>, tools: &HashMap<String, Py<PyAny>>, call: &ToolCall) -> ToolResult {
+    let output = match tools.get(&call.name) {
+        Some(function) => invoke(py, function.bind(py), &call.arguments).map_err(|e| e.to_string()),
+        None => Err(format!("Unknown tool: {}", call.name)),
+    };
+    match output {
+        Ok(content) => ToolResult::new(&call.id, &content, Some(&call.name), false),
+        Err(e) => ToolResult::new(&call.id, &e, Some(&call.name), true),
+    }
+}
+
+fn invoke(py: Python<'_>, function: &Bound<'_, PyAny>, arguments: &Value) -> PyResult<String> {
+    let arguments = pythonize(py, arguments)?;
+    let output = match arguments.downcast::<PyDict>() {
+        Ok(kwargs) => function.call((), Some(kwargs))?,
+        // the arguments were not valid JSON, pass them as they are
+        Err(_) => function.call1((arguments,))?,
+    };
+
+    if let Ok(text) = output.downcast::<PyString>() {
+        return Ok(text.to_string());
+    }
+    if output.is_none() {
+        return Ok(String::new());
+    }
+    match depythonize::<Value>(&output) {
+        Ok(value) => Ok(value.to_string()),
+        Err(_) => Ok(output.str()?.to_string()),
+    }
+}
+
+/// Execute the tool calls of one response, in parallel threads if there are several of them
+fn call_tools(
+    py: Python<'_>,
+    tools: &HashMap<String, Py<PyAny>>,
+    calls: &[ToolCall],
+) -> PyResult<Vec<ToolResult>> {
+    if calls.len() == 1 {
+        return Ok(vec![call_tool(py, tools, &calls[0])]);
+    }
+    py.detach(|| {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = calls
+                .iter()
+                .map(|call| scope.spawn(move || Python::attach(|py| call_tool(py, tools, call))))
+                .collect();
+            // a panic must not unwind into the interpreter
+            handles
+                .into_iter()
+                .zip(calls)
+                .map(|(handle, call)| {
+                    handle.join().map_err(|_| {
+                        PyRuntimeError::new_err(format!(
+                            "The thread of the tool {} panicked",
+                            call.name
+                        ))
+                    })
+                })
+                .collect()
+        })
+    })
+}
+
+/// Send the request, execute the tools called by the model and send their results back,
+/// until the model answers without calling a tool or a limit is reached
+///
+/// The conversation, including the tool calls and results, is written back into `request_body`
+#[pyfunction]
+#[pyo3(signature = (request_body, tools, max_steps=10, max_cost=None, http=None))]
+pub fn run_agent(
+    py: Python<'_>,
+    request_body: Bound<'_, PyAny>,
+    tools: HashMap<String, Py<PyAny>>,
+    max_steps: usize,
+    max_cost: Option<f64>,
+    http: Option<HttpSettings>,
+) -> PyResult<AgentResult> {
+    http::sync_scope(http, || {
+        let mut request = ProviderRequest::extract(&request_body)?;
+        // tools are only supported in chat mode with Ollama
+        let result = run_steps(py, &mut request, &tools, max_steps, max_cost, |request| {
+            request.send(true)
+        })?;
+        request.store(&request_body)?;
+        Ok(result)
+    })
+}
+
+/// The loop of `run_agent`, with the request sent by `send`
+fn run_steps(
+    py: Python<'_>,
+    request: &mut ProviderRequest,
+    tools: &HashMap<String, Py<PyAny>>,
+    max_steps: usize,
+    max_cost: Option<f64>,
+    mut send: impl FnMut(&ProviderRequest) -> Result<LLMResponse> + Send,
+) -> PyResult<AgentResult> {
+    if max_steps == 0 {
+        return Err(PyValueError::new_err("max_steps must be at least 1"));
+    }
+    // rather than failing after the first paid request
+    let model = request.model();
+    if max_cost.is_some()
+        && !model
+            .info()
+            .is_some_and(|info| info.input_price.is_some() && info.output_price.is_some())
+    {
+        return Err(PyValueError::new_err(format!(
+            "max_cost requires the prices of the model, none are known for {} (see load_models)",
+            model.to_str()
+        )));
+    }
+    let mut steps: Vec<AgentStep> = Vec::new();
+    let mut usage = Usage::default();
+    let mut cost = 0.0;
+
+    let stop_reason = loop {
+        py.check_signals()?;
+        let response = py.detach(|| send(request)).map_err(to_py_err)?;
+        usage += response.usage.clone();
+        if max_cost.is_some() {
+            cost += response.cost()?;
+        }
+
+        if response.tool_calls.is_empty() {
+            request.add_response(response.clone())?;
+            steps.push(AgentStep {
+                response,
+                tool_results: vec![],
+            });
+            break "completed";
+        }
+        // the pending tool calls are not executed, and not added to the request since a
+        // tool call without result is rejected by the providers
+        let limit = if steps.len() + 1 >= max_steps {
+            Some("max_steps")
+        } else if max_cost.is_some_and(|max_cost| cost >= max_cost) {
+            Some("max_cost")
+        } else {
+            None
+        };
+        if let Some(limit) = limit {
+            steps.push(AgentStep {
+                response,
+                tool_results: vec![],
+            });
+            break limit;
+        }
+
+        let tool_results = call_tools(py, tools, &response.tool_calls)?;
+        request.add_response(response.clone())?;
+        request.add_message(Message {
+            role: "user".to_string(),
+            content: tool_results
+                .iter()
+                .map(|result| Content {
+                    ctx: ContentTypeInner::ToolResult(result.clone()),
+                    cache_control: None,
+                })
+                .collect(),
+        });
+        steps.push(AgentStep {
+            response,
+            tool_results,
+        });
+    };
+
+    Ok(AgentResult {
+        response: steps.last().unwrap().response.clone(),
+        steps,
+        stop_reason: stop_reason.to_string(),
+        usage,
+    })
+}
+
+#[test]
+fn test_run_steps() {
+    use crate::SupportedModels;
+    use crate::mistral::structs::MistralRequest;
+    use serde_json::json;
+    use std::collections::VecDeque;
+
+    let calls = |calls: &[(&str, &str, Value)]| LLMResponse {
+        model: SupportedModels::MistralSmallLatest,
+        role: Some("assistant".to_string()),
+        usage: Usage {
+            input_tokens: 1000,
+            output_tokens: 100,
+            ..Default::default()
+        },
+        tool_calls: calls
+            .iter()
+            .map(|(id, name, arguments)| ToolCall {
+                id: id.to_string(),
+                name: name.to_string(),
+                arguments: arguments.clone(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let request = |model: SupportedModels| {
+        ProviderRequest::Mistral(
+            MistralRequest::new(model.to_str(), vec![], None, None, None, None, None).unwrap(),
+        )
+    };
+
+    Python::initialize();
+    Python::attach(|py| {
+        let weather = py
+            .eval(c"lambda city: f'Sunny in {city}'", None, None)
+            .unwrap();
+        let tools = HashMap::from([("get_weather".to_string(), weather.unbind())]);
+
+        // the tools are called with the arguments of the model, an unknown one is an error result
+        let mut responses = VecDeque::from([
+            calls(&[
+                ("call_1", "get_weather", json!({"city": "Berlin"})),
+                ("call_2", "get_time", json!({})),
+            ]),
+            calls(&[]),
+        ]);
+        let mut mistral = request(SupportedModels::MistralSmallLatest);
+        let result = run_steps(py, &mut mistral, &tools, 10, None, |_| {
+            Ok(responses.pop_front().unwrap())
+        })
+        .unwrap();
+        assert_eq!(result.stop_reason, "completed");
+        assert_eq!(result.steps.len(), 2);
+        assert_eq!(result.usage.input_tokens, 2000);
+        let tool_results = &result.steps[0].tool_results;
+        assert_eq!(tool_results[0].content, "Sunny in Berlin");
+        assert!(!tool_results[0].is_error);
+        assert_eq!(tool_results[1].content, "Unknown tool: get_time");
+        assert!(tool_results[1].is_error);
+        let ProviderRequest::Mistral(mistral) = mistral else {
+            unreachable!()
+        };
+        // the first answer, the tool results and the final answer
+        assert_eq!(mistral.messages.len(), 3);
+
+        let always_calls = |_: &ProviderRequest| {
+            Ok(calls(&[(
+                "call_1",
+                "get_weather",
+                json!({"city": "Paris"}),
+            )]))
+        };
+        let result = run_steps(
+            py,
+            &mut request(SupportedModels::MistralSmallLatest),
+            &tools,
+            3,
+            None,
+            always_calls,
+        )
+        .unwrap();
+        assert_eq!(result.stop_reason, "max_steps");
+        assert_eq!(result.steps.len(), 3);
+        assert!(result.steps[2].tool_results.is_empty());
+
+        // $0.00013 per step
+        let result = run_steps(
+            py,
+            &mut request(SupportedModels::MistralSmallLatest),
+            &tools,
+            10,
+            Some(0.0002),
+            always_calls,
+        )
+        .unwrap();
+        assert_eq!(result.stop_reason, "max_cost");
+        assert_eq!(result.steps.len(), 2);
+
+        let error = run_steps(
+            py,
+            &mut request(SupportedModels::Custom("my-finetune".to_string())),
+            &tools,
+            10,
+            Some(1.0),
+            |_| panic!("an unpriced model must not be sent with max_cost"),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("my-finetune"));
+    });
+}
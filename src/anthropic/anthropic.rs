@@ -1,105 +1,102 @@
-use anyhow::{Result, anyhow};
-use reqwest;
+use anyhow::Result;
+use reqwest::Response;
 use serde::{Deserialize, Serialize};
-use std::env;
+use serde_json::{Value, json};
 
 use crate::ANTHROPIC_API_URL;
 use crate::SupportedModels;
 use crate::anthropic::structs::AnthropicRequest;
 use crate::message::Message;
 use crate::response::LLMResponse;
+use crate::retry::send_with_retry;
+use crate::runtime::{client, runtime};
+use crate::tools::{Tool, serialize_anthropic_tools};
 
-#[tokio::main]
-pub async fn get_response_anthropic(request_body: AnthropicRequest) -> Result<LLMResponse> {
-    request_anthropic(request_body).await
+pub fn get_response_anthropic(request_body: AnthropicRequest) -> Result<LLMResponse> {
+    runtime().block_on(request_anthropic(request_body))
 }
 
-#[tokio::main]
-pub async fn get_count_tokens_anthropic(request_body: AnthropicRequest) -> Result<u32> {
-    count_tokens_anthropic(request_body).await
+pub fn get_count_tokens_anthropic(request_body: AnthropicRequest) -> Result<u32> {
+    runtime().block_on(count_tokens_anthropic(request_body))
 }
 
-async fn request_anthropic(request_body: AnthropicRequest) -> Result<LLMResponse> {
-    let api_key = env::var("ANTHROPIC_API_KEY").unwrap_or("".to_string());
-    if api_key.is_empty() {
-        return Err(anyhow!(
-            "ANTHROPIC_API_KEY environment variable must be set"
-        ));
-    }
+pub(crate) async fn request_anthropic(request_body: AnthropicRequest) -> Result<LLMResponse> {
+    let response = post_anthropic(&request_body).await?;
+    // let response_text = response.text().await?;
+    // println!("Raw response: {}", response_text);
+    // let response: LLMResponse = serde_json::from_str(&response_text)?;
+    let response = LLMResponse::from_json(response.json().await?)?;
+    Ok(response)
+}
+
+/// Send the request with `stream` enabled and hand back the raw SSE response
+pub(crate) async fn request_anthropic_stream(
+    mut request_body: AnthropicRequest,
+) -> Result<Response> {
+    request_body.stream = Some(true);
+    post_anthropic(&request_body).await
+}
+
+async fn post_anthropic(request_body: &AnthropicRequest) -> Result<Response> {
+    let api_key = crate::client::api_key("anthropic", "ANTHROPIC_API_KEY")?;
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(ANTHROPIC_API_URL)
+    let url = format!(
+        "{}/messages",
+        crate::client::base_url("anthropic", ANTHROPIC_API_URL)
+    );
+    let request = client("anthropic", &url)?
+        .post(&url)
         .header("x-api-key", api_key)
         .header("anthropic-version", "2023-06-01")
         .header("content-type", "application/json")
-        .json(&request_body)
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        // let response_text = response.text().await?;
-        // println!("Raw response: {}", response_text);
-        // let response: LLMResponse = serde_json::from_str(&response_text)?;
-        let response: LLMResponse = response.json().await?;
-        Ok(response)
-    } else {
-        let err_status = response.status();
-        let error_text = response.text().await?;
-        Err(anyhow!(
-            "Error: HTTP {}, Response: {}",
-            err_status,
-            error_text
-        ))
-    }
+        .json(&request_body.to_body()?);
+
+    send_with_retry("anthropic", request).await
 }
 
-async fn count_tokens_anthropic(request_body: AnthropicRequest) -> Result<u32> {
-    let api_key = env::var("ANTHROPIC_API_KEY").unwrap_or("".to_string());
-    if api_key.is_empty() {
-        return Err(anyhow!(
-            "ANTHROPIC_API_KEY environment variable must be set"
-        ));
-    }
+pub(crate) async fn count_tokens_anthropic(request_body: AnthropicRequest) -> Result<u32> {
+    let api_key = crate::client::api_key("anthropic", "ANTHROPIC_API_KEY")?;
 
     #[derive(Serialize, Debug)]
     struct CountTokensRequest {
         model: SupportedModels,
         messages: Vec<Message>,
+        #[serde(
+            skip_serializing_if = "Vec::is_empty",
+            serialize_with = "serialize_anthropic_tools"
+        )]
+        tools: Vec<Tool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        thinking: Option<Value>,
     }
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(format!("{}/count_tokens", ANTHROPIC_API_URL))
+    let url = format!(
+        "{}/messages/count_tokens",
+        crate::client::base_url("anthropic", ANTHROPIC_API_URL)
+    );
+    let request = client("anthropic", &url)?
+        .post(&url)
         .header("x-api-key", api_key)
         .header("anthropic-version", "2023-06-01")
         .header("content-type", "application/json")
         .json(&CountTokensRequest {
             model: request_body.model,
             messages: request_body.messages,
-        })
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        #[derive(Deserialize, Debug)]
-        struct CountTokensResponse {
-            input_tokens: u32,
-        }
-        // let response_text = response.text().await?;
-        // println!("Raw response: {}", response_text);
-        // let response: LLMResponse = serde_json::from_str(&response_text)?;
-        let response: CountTokensResponse = response.json().await?;
-        Ok(response.input_tokens)
-    } else {
-        let err_status = response.status();
-        let error_text = response.text().await?;
-        Err(anyhow!(
-            "Error: HTTP {}, Response: {}",
-            err_status,
-            error_text
-        ))
+            tools: request_body.tools,
+            thinking: request_body
+                .thinking_budget
+                .map(|budget| json!({"type": "enabled", "budget_tokens": budget})),
+        });
+
+    #[derive(Deserialize, Debug)]
+    struct CountTokensResponse {
+        input_tokens: u32,
     }
+    // let response_text = response.text().await?;
+    // println!("Raw response: {}", response_text);
+    // let response: LLMResponse = serde_json::from_str(&response_text)?;
+    let response: CountTokensResponse = send_with_retry("anthropic", request).await?.json().await?;
+    Ok(response.input_tokens)
 }
 
 #[tokio::test]
@@ -116,16 +113,24 @@ async fn test_request_anthropic() {
                     ctx: ContentTypeInner::Document(
                         DocumentContent::new("test.pdf", None).unwrap(),
                     ),
+                    cache_control: None,
                 },
                 Content {
                     ctx: ContentTypeInner::Text(TextContent {
                         content_type: "text".to_string(),
                         text: "What is the recipient address from this invoice?".to_string(),
                     }),
+                    cache_control: None,
                 },
             ],
         }],
         system: Some("Please answer in Chinese".to_string()),
+        tools: vec![],
+        tool_choice: None,
+        stream: None,
+        generation_config: None,
+        thinking_budget: None,
+        prompt_cache_control: None,
     };
 
     println!(
@@ -147,6 +152,7 @@ async fn test_request_anthropic() {
                         text: "I want you to answer the same question again but in English"
                             .to_string(),
                     }),
+                    cache_control: None,
                 }],
             });
             println!(
@@ -1,9 +1,17 @@
+use anyhow::anyhow;
 use pyo3::prelude::*;
+use serde::de::{self, Deserializer};
 use serde::{Deserialize, Serialize};
 
 use crate::SupportedModels;
-use crate::message::{Content, ContentTypeInner, Message, TextContent};
+use crate::generation::{GenerationConfig, with_params};
+use crate::message::{
+    Content, ContentTypeInner, Message, TextContent, cache_control, check_cache_control,
+};
 use crate::response::LLMResponse;
+use crate::tools::{Tool, serialize_anthropic_tool_choice, serialize_anthropic_tools};
+use serde::Serializer;
+use serde_json::{Value, json};
 
 #[derive(Serialize, Clone, Debug)]
 #[pyclass(dict, get_all, set_all, subclass)]
@@ -12,25 +20,85 @@ pub struct AnthropicRequest {
     pub(crate) system: Option<String>,
     pub(crate) max_tokens: u32,
     pub(crate) messages: Vec<Message>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_anthropic_tools"
+    )]
+    pub(crate) tools: Vec<Tool>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_anthropic_tool_choice"
+    )]
+    pub(crate) tool_choice: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) stream: Option<bool>,
+    #[serde(skip)]
+    pub(crate) generation_config: Option<GenerationConfig>, // added to the body by post_anthropic
+    #[serde(
+        rename = "thinking",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_thinking"
+    )]
+    pub(crate) thinking_budget: Option<u32>, // extended thinking, counts towards max_tokens
+    #[serde(skip)]
+    pub(crate) prompt_cache_control: Option<String>, // caches the system prompt, see to_body
+}
+
+fn serialize_thinking<S>(thinking_budget: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match thinking_budget {
+        None => serializer.serialize_none(),
+        Some(budget) => json!({"type": "enabled", "budget_tokens": budget}).serialize(serializer),
+    }
 }
 
 #[pymethods]
 impl AnthropicRequest {
     #[new]
-    #[pyo3(signature = (model,messages,max_tokens=1024,prompt=None))]
+    #[pyo3(signature = (model,messages,max_tokens=1024,prompt=None,tools=None,tool_choice=None,generation_config=None,thinking_budget=None,prompt_cache_control=None))]
+    #[allow(clippy::too_many_arguments)]
     fn new(
         model: &str,
         messages: Vec<Message>,
         max_tokens: Option<u32>,
         prompt: Option<&str>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<&str>,
+        generation_config: Option<GenerationConfig>,
+        thinking_budget: Option<u32>,
+        prompt_cache_control: Option<&str>,
     ) -> PyResult<Self> {
-        
         let max_tokens = max_tokens.unwrap_or(1024);
+        let model = SupportedModels::from_str(model)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if let Some(config) = &generation_config {
+            config
+                .to_anthropic()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+        if let Some(budget) = thinking_budget {
+            check_thinking(
+                &model,
+                budget,
+                max_tokens,
+                tool_choice,
+                generation_config.as_ref(),
+            )
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
         Ok(Self {
-            model: SupportedModels::from_str(model).unwrap(),
+            model,
             max_tokens,
             messages,
             system: prompt.map(|s| s.to_string()),
+            tools: tools.unwrap_or_default(),
+            tool_choice: tool_choice.map(|s| s.to_string()),
+            stream: None,
+            generation_config,
+            thinking_budget,
+            prompt_cache_control: check_cache_control(prompt_cache_control)?,
         })
     }
 
@@ -40,14 +108,38 @@ impl AnthropicRequest {
 
     pub fn add_response(&mut self, response: LLMResponse) {
         // TODO! refactor is necessary
-        self.messages.push(Message {
-            role: response.role.unwrap(),
-            content: vec![Content {
-                ctx: ContentTypeInner::Text(TextContent {
-                    content_type: "text".to_string(),
-                    text: response.content.unwrap()[0].text.clone(),
+        // the thinking blocks must be sent back unchanged and before the rest of the message
+        let mut content: Vec<Content> = response
+            .thinking
+            .into_iter()
+            .map(|thinking| Content {
+                ctx: ContentTypeInner::Thinking(thinking),
+                cache_control: None,
+            })
+            .collect();
+        content.extend(
+            response
+                .content
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|block| !block.text.is_empty()) // empty text blocks are rejected by Anthropic
+                .map(|block| Content {
+                    ctx: ContentTypeInner::Text(TextContent {
+                        content_type: "text".to_string(),
+                        text: block.text,
+                    }),
+                    cache_control: None,
                 }),
-            }],
+        );
+        // the tool_use blocks must be kept for the following tool_result blocks
+        content.extend(response.tool_calls.into_iter().map(|call| Content {
+            ctx: ContentTypeInner::ToolCall(call),
+            cache_control: None,
+        }));
+
+        self.messages.push(Message {
+            role: response.role.unwrap_or("assistant".to_string()),
+            content,
         });
     }
 
@@ -56,6 +148,86 @@ impl AnthropicRequest {
     }
 }
 
+impl AnthropicRequest {
+    /// The body of the Messages API, with the GenerationConfig added and the system prompt as a
+    /// text block when it is cached
+    pub(crate) fn to_body(&self) -> anyhow::Result<Value> {
+        let params = self
+            .generation_config
+            .as_ref()
+            .map(GenerationConfig::to_anthropic)
+            .transpose()?;
+        let mut body = with_params(self, params)?;
+        if let (Some(system), Some(ttl)) = (&self.system, &self.prompt_cache_control) {
+            body["system"] =
+                json!([{"type": "text", "text": system, "cache_control": cache_control(ttl)}]);
+        }
+
+        let breakpoints = self.prompt_cache_control.iter().count()
+            + self
+                .messages
+                .iter()
+                .flat_map(|message| &message.content)
+                .filter(|content| content.cache_control.is_some())
+                .count();
+        if breakpoints > MAX_CACHE_BREAKPOINTS {
+            return Err(anyhow!(
+                "At most {} blocks can be marked with cache_control, got {}",
+                MAX_CACHE_BREAKPOINTS,
+                breakpoints
+            ));
+        }
+        Ok(body)
+    }
+}
+
+/// The number of cache breakpoints accepted in one request, see https://docs.claude.com/en/docs/build-with-claude/prompt-caching
+const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+/// The limits of extended thinking, see https://docs.claude.com/en/docs/build-with-claude/extended-thinking
+fn check_thinking(
+    model: &SupportedModels,
+    budget: u32,
+    max_tokens: u32,
+    tool_choice: Option<&str>,
+    generation_config: Option<&GenerationConfig>,
+) -> anyhow::Result<()> {
+    if model.info().is_some_and(|info| !info.reasoning) {
+        return Err(anyhow!(
+            "{} does not support extended thinking",
+            model.to_str()
+        ));
+    }
+    if budget < 1024 || budget >= max_tokens {
+        return Err(anyhow!(
+            "thinking_budget must be at least 1024 and less than max_tokens ({}), got {}",
+            max_tokens,
+            budget
+        ));
+    }
+    if tool_choice.is_some_and(|tool_choice| !["auto", "none"].contains(&tool_choice)) {
+        return Err(anyhow!(
+            "Only \"auto\" and \"none\" are supported as tool_choice with extended thinking"
+        ));
+    }
+    if let Some(config) = generation_config {
+        if config.temperature.is_some() {
+            return Err(anyhow!(
+                "temperature is not supported with extended thinking"
+            ));
+        }
+        if config.top_k.is_some() {
+            return Err(anyhow!("top_k is not supported with extended thinking"));
+        }
+        if config.top_p.is_some_and(|top_p| top_p < 0.95) {
+            return Err(anyhow!(
+                "top_p must be between 0.95 and 1 with extended thinking"
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[pyclass(dict, get_all, set_all)]
 pub struct ResponseAnthropic {
@@ -70,3 +242,154 @@ impl ResponseAnthropic {
         Ok(format!("ResponseAnthropic<text={:?}>", self.text))
     }
 }
+
+// Custom deserializer for Anthropic content that only extracts text blocks and not tool_use
+// (tool calls are collected separately by LLMResponse::from_json)
+pub fn deserialize_text_only<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<ResponseAnthropic>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let blocks = Option::<Vec<serde_json::Value>>::deserialize(deserializer)?;
+    blocks
+        .map(|blocks| {
+            blocks
+                .into_iter()
+                .filter(|block| block["type"] == "text")
+                .map(|block| serde_json::from_value(block).map_err(de::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
+#[test]
+fn test_thinking_anthropic() {
+    let mut request = AnthropicRequest::new(
+        "claude-sonnet-4-5",
+        vec![Message {
+            role: "user".to_string(),
+            content: vec![Content {
+                ctx: ContentTypeInner::Text(TextContent::new("Weather in Berlin?").unwrap()),
+                cache_control: None,
+            }],
+        }],
+        Some(4096),
+        None,
+        None,
+        None,
+        None,
+        Some(2048),
+        None,
+    )
+    .unwrap();
+    let response = LLMResponse::from_json(json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-sonnet-4-5-20250929",
+        "content": [
+            {"type": "thinking", "thinking": "Berlin is in Germany", "signature": "sig_1"},
+            {"type": "redacted_thinking", "data": "encrypted"},
+            {"type": "text", "text": "Sunny"},
+        ],
+        "stop_reason": "end_turn",
+        "usage": {"input_tokens": 10, "output_tokens": 20},
+    }))
+    .unwrap();
+    assert_eq!(response.to_string(), "Sunny");
+    assert_eq!(response.thinking.len(), 2);
+    request.add_response(response);
+
+    let body = serde_json::to_value(&request).unwrap();
+    assert_eq!(
+        body["thinking"],
+        json!({"type": "enabled", "budget_tokens": 2048})
+    );
+    assert_eq!(
+        body["messages"][1]["content"],
+        json!([
+            {"type": "thinking", "thinking": "Berlin is in Germany", "signature": "sig_1"},
+            {"type": "redacted_thinking", "data": "encrypted"},
+            {"type": "text", "text": "Sunny"},
+        ])
+    );
+
+    let new = |model: &str, budget: u32| {
+        AnthropicRequest::new(
+            model,
+            vec![],
+            Some(4096),
+            None,
+            None,
+            None,
+            None,
+            Some(budget),
+            None,
+        )
+    };
+    assert!(new("claude-3-5-haiku-latest", 2048).is_err());
+    assert!(new("claude-sonnet-4-5", 512).is_err());
+    assert!(new("claude-sonnet-4-5", 4096).is_err());
+}
+
+#[test]
+fn test_prompt_caching_anthropic() {
+    let document = Content {
+        ctx: ContentTypeInner::Text(TextContent::new("A long document").unwrap()),
+        cache_control: Some("1h".to_string()),
+    };
+    let question = Content {
+        ctx: ContentTypeInner::Text(TextContent::new("Summarize it").unwrap()),
+        cache_control: None,
+    };
+    let mut request = AnthropicRequest::new(
+        "claude-haiku-4-5",
+        vec![Message {
+            role: "user".to_string(),
+            content: vec![document.clone(), question],
+        }],
+        None,
+        Some("Be brief"),
+        None,
+        None,
+        None,
+        None,
+        Some("5m"),
+    )
+    .unwrap();
+    let body = request.to_body().unwrap();
+    assert_eq!(
+        body["system"],
+        json!([{"type": "text", "text": "Be brief", "cache_control": {"type": "ephemeral"}}])
+    );
+    assert_eq!(
+        body["messages"][0]["content"][0]["cache_control"],
+        json!({"type": "ephemeral", "ttl": "1h"})
+    );
+    assert!(
+        body["messages"][0]["content"][1]
+            .get("cache_control")
+            .is_none()
+    );
+
+    request.messages[0].content.extend(vec![document; 3]);
+    assert!(request.to_body().is_err());
+
+    let response = LLMResponse::from_json(json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-haiku-4-5",
+        "content": [{"type": "text", "text": "Short"}],
+        "stop_reason": "end_turn",
+        "usage": {"input_tokens": 100, "cache_creation_input_tokens": 1000, "cache_read_input_tokens": 2000, "output_tokens": 10,
+            "cache_creation": {"ephemeral_5m_input_tokens": 400, "ephemeral_1h_input_tokens": 600}},
+    }))
+    .unwrap();
+    assert_eq!(response.usage.input_tokens, 3100);
+    assert_eq!(response.usage.cache_read_input_tokens, 2000);
+    assert_eq!(response.usage.details["ephemeral_1h_input_tokens"], 600);
+    // 100 at $1, 400 at $1.25, 600 at $2, 2000 at $0.10 and 10 at $5 per million
+    assert!((response.cost().unwrap() - 0.00205).abs() < 1e-12);
+}
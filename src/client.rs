@@ -0,0 +1,471 @@
+use anyhow::{Result, anyhow};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use reqwest::RequestBuilder;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::error::{ErrorKind, LocalError};
+use crate::http::HttpSettings;
+use crate::openai::azure::AzureSettings;
+use crate::retry::RetryPolicy;
+use crate::{agent, stream};
+
+tokio::task_local! {
+    // the client whose method is running, None for the module-level functions
+    static CURRENT: Option<Arc<GoldenAIClient>>;
+}
+
+/// The credentials, base URL, headers and HTTP settings of one provider of a `GoldenAIClient`
+#[derive(Clone, Default, PartialEq)]
+#[pyclass(dict, frozen)]
+pub struct ProviderSettings {
+    pub api_key: Option<String>, // None for the environment variable of the provider, not readable
+    #[pyo3(get)]
+    pub base_url: Option<String>, // the root of the API, e.g. "https://api.openai.com/v1"
+    #[pyo3(get)]
+    pub headers: BTreeMap<String, String>, // sent with every request, unless it sets them itself
+    #[pyo3(get)]
+    pub http: Option<HttpSettings>, // None for the ones of the client
+}
+
+#[pymethods]
+impl ProviderSettings {
+    #[new]
+    #[pyo3(signature = (api_key=None, base_url=None, headers=None, http=None))]
+    fn new(
+        api_key: Option<String>,
+        base_url: Option<String>,
+        headers: Option<BTreeMap<String, String>>,
+        http: Option<HttpSettings>,
+    ) -> PyResult<Self> {
+        let settings = Self {
+            api_key,
+            base_url: base_url.map(|url| url.trim_end_matches('/').to_string()),
+            headers: headers.unwrap_or_default(),
+            http,
+        };
+        settings
+            .header_map()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        if let Some(url) = &settings.base_url {
+            reqwest::Url::parse(url)
+                .map_err(|e| PyValueError::new_err(format!("Invalid base_url {}: {}", url, e)))?;
+        }
+        Ok(settings)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        // the key is not shown, reprs end up in logs
+        Ok(format!(
+            "ProviderSettings<api_key={},base_url={},headers={:?}>",
+            if self.api_key.is_some() {
+                "***"
+            } else {
+                "None"
+            },
+            self.base_url.as_deref().unwrap_or("None"),
+            self.headers.keys().collect::<Vec<_>>()
+        ))
+    }
+}
+
+impl std::fmt::Debug for ProviderSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProviderSettings")
+            .field("api_key", &self.api_key.as_ref().map(|_| "***"))
+            .field("base_url", &self.base_url)
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("http", &self.http)
+            .finish()
+    }
+}
+
+impl ProviderSettings {
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::try_from(name).map_err(|_| anyhow!("Invalid header name {}", name))?,
+                HeaderValue::try_from(value)
+                    .map_err(|_| anyhow!("Invalid value of the header {}", name))?,
+            );
+        }
+        Ok(headers)
+    }
+}
+
+/// Credentials and settings of the providers for the requests sent by its methods, instead of
+/// the environment variables and the global settings used by the module-level functions
+#[derive(Clone, Debug, Default)]
+#[pyclass(dict, get_all, frozen)]
+pub struct GoldenAIClient {
+    pub openai: Option<ProviderSettings>,
+    pub anthropic: Option<ProviderSettings>,
+    pub gemini: Option<ProviderSettings>,
+    pub mistral: Option<ProviderSettings>,
+    pub ollama: Option<ProviderSettings>, // the URL is the one of the OllamaRequest
+    pub openai_compat: Option<ProviderSettings>, // the URL is the one of the OpenAICompatRequest
+    pub azure_openai: Option<AzureSettings>, // sends the OpenAIRequests to Azure instead
+    pub http: Option<HttpSettings>,       // None for the ones of `set_http_settings`
+    pub retry: Option<RetryPolicy>,       // None for the one of `set_retry_policy`
+}
+
+#[pymethods]
+impl GoldenAIClient {
+    #[new]
+    #[pyo3(signature = (openai=None, anthropic=None, gemini=None, mistral=None, ollama=None, openai_compat=None, azure_openai=None, http=None, retry=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        openai: Option<ProviderSettings>,
+        anthropic: Option<ProviderSettings>,
+        gemini: Option<ProviderSettings>,
+        mistral: Option<ProviderSettings>,
+        ollama: Option<ProviderSettings>,
+        openai_compat: Option<ProviderSettings>,
+        azure_openai: Option<AzureSettings>,
+        http: Option<HttpSettings>,
+        retry: Option<RetryPolicy>,
+    ) -> PyResult<Self> {
+        // Ollama and OpenAI-compatible servers are sent to the URL of their request, and Ollama
+        // takes no API key, such settings would be ignored without notice
+        let ignored = [
+            (
+                ollama.as_ref().is_some_and(|s| s.base_url.is_some()),
+                "base_url of ollama is not supported, the URL is the one of the OllamaRequest",
+            ),
+            (
+                ollama.as_ref().is_some_and(|s| s.api_key.is_some()),
+                "api_key of ollama is not supported, Ollama takes no API key",
+            ),
+            (
+                openai_compat.as_ref().is_some_and(|s| s.base_url.is_some()),
+                "base_url of openai_compat is not supported, the URL is the one of the OpenAICompatRequest",
+            ),
+        ];
+        if let Some((_, message)) = ignored.iter().find(|(is_set, _)| *is_set) {
+            return Err(PyValueError::new_err(*message));
+        }
+        Ok(Self {
+            openai,
+            anthropic,
+            gemini,
+            mistral,
+            ollama,
+            openai_compat,
+            azure_openai,
+            http,
+            retry,
+        })
+    }
+
+    #[pyo3(signature = (request_body, schema=None, max_reasks=0, http=None))]
+    fn send<'p>(
+        &self,
+        py: Python<'p>,
+        request_body: Bound<'p, PyAny>,
+        schema: Option<Bound<'p, PyAny>>,
+        max_reasks: u32,
+        http: Option<HttpSettings>,
+    ) -> PyResult<crate::response::LLMResponse> {
+        self.sync_scope(|| crate::send(py, request_body, schema, max_reasks, http))
+    }
+
+    #[pyo3(signature = (request_body, http=None))]
+    fn chat(
+        &self,
+        py: Python<'_>,
+        request_body: Bound<PyAny>,
+        http: Option<HttpSettings>,
+    ) -> PyResult<crate::response::LLMResponse> {
+        self.sync_scope(|| crate::chat(py, request_body, http))
+    }
+
+    #[pyo3(signature = (request_body, chat=false, http=None))]
+    fn stream<'p>(
+        &self,
+        py: Python<'p>,
+        request_body: Bound<'p, PyAny>,
+        chat: bool,
+        http: Option<HttpSettings>,
+    ) -> PyResult<stream::LLMStream> {
+        self.sync_scope(|| crate::send_stream(py, request_body, chat, http))
+    }
+
+    #[pyo3(signature = (request_body, http=None))]
+    fn count_tokens<'p>(
+        &self,
+        py: Python<'p>,
+        request_body: Bound<'p, PyAny>,
+        http: Option<HttpSettings>,
+    ) -> PyResult<u32> {
+        self.sync_scope(|| crate::count_tokens(py, request_body, http))
+    }
+
+    #[pyo3(signature = (request_body, http=None))]
+    fn send_async<'p>(
+        &self,
+        py: Python<'p>,
+        request_body: Bound<'p, PyAny>,
+        http: Option<HttpSettings>,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        self.sync_scope(|| crate::send_async(py, request_body, http))
+    }
+
+    #[pyo3(signature = (request_body, http=None))]
+    fn chat_async<'p>(
+        &self,
+        py: Python<'p>,
+        request_body: Bound<'p, PyAny>,
+        http: Option<HttpSettings>,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        self.sync_scope(|| crate::chat_async(py, request_body, http))
+    }
+
+    #[pyo3(signature = (request_body, http=None))]
+    fn count_tokens_async<'p>(
+        &self,
+        py: Python<'p>,
+        request_body: Bound<'p, PyAny>,
+        http: Option<HttpSettings>,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        self.sync_scope(|| crate::count_tokens_async(py, request_body, http))
+    }
+
+    #[pyo3(signature = (request_body, tools, max_steps=10, max_cost=None, http=None))]
+    fn run_agent(
+        &self,
+        py: Python<'_>,
+        request_body: Bound<'_, PyAny>,
+        tools: HashMap<String, Py<PyAny>>,
+        max_steps: usize,
+        max_cost: Option<f64>,
+        http: Option<HttpSettings>,
+    ) -> PyResult<agent::AgentResult> {
+        self.sync_scope(|| agent::run_agent(py, request_body, tools, max_steps, max_cost, http))
+    }
+
+    #[pyo3(signature = (document, model=crate::mistral::mistral::MISTRAL_OCR_MODEL, http=None))]
+    fn ocr(
+        &self,
+        py: Python<'_>,
+        document: Bound<'_, PyAny>,
+        model: &str,
+        http: Option<HttpSettings>,
+    ) -> PyResult<Vec<String>> {
+        self.sync_scope(|| crate::ocr(py, document, model, http))
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        let mut providers: Vec<&str> = PROVIDERS
+            .iter()
+            .filter(|provider| self.provider(provider).is_some())
+            .copied()
+            .collect();
+        if self.azure_openai.is_some() {
+            providers.push("azure_openai");
+        }
+        Ok(format!("GoldenAIClient<providers={:?}>", providers))
+    }
+}
+
+const PROVIDERS: [&str; 6] = [
+    "openai",
+    "anthropic",
+    "gemini",
+    "mistral",
+    "ollama",
+    "openai_compat",
+];
+
+impl GoldenAIClient {
+    fn provider(&self, provider: &str) -> Option<&ProviderSettings> {
+        match provider {
+            "openai" => self.openai.as_ref(),
+            "anthropic" => self.anthropic.as_ref(),
+            "gemini" => self.gemini.as_ref(),
+            "mistral" => self.mistral.as_ref(),
+            "ollama" => self.ollama.as_ref(),
+            "openai_compat" => self.openai_compat.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Run `f` with this client for every request sent on this thread, e.g. by a `block_on`
+    fn sync_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        CURRENT.sync_scope(Some(Arc::new(self.clone())), f)
+    }
+}
+
+/// The client whose method is running, if any
+pub(crate) fn current() -> Option<Arc<GoldenAIClient>> {
+    CURRENT.try_with(|client| client.clone()).ok().flatten()
+}
+
+/// Run `future` with `client` for every request it sends
+pub(crate) fn scope<F: Future>(
+    client: Option<Arc<GoldenAIClient>>,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    CURRENT.scope(client, future)
+}
+
+/// The settings of `provider` of the current client
+pub(crate) fn settings(provider: &str) -> Option<ProviderSettings> {
+    current()?.provider(provider).cloned()
+}
+
+/// The API key of the current client, else the environment variable `variable`
+pub(crate) fn api_key(provider: &str, variable: &str) -> Result<String> {
+    settings(provider)
+        .and_then(|settings| settings.api_key)
+        .or_else(|| std::env::var(variable).ok())
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| {
+            LocalError::new(
+                ErrorKind::Authentication,
+                format!("{} environment variable must be set", variable),
+            )
+            .into()
+        })
+}
+
+/// The base URL of the current client, else `default`
+pub(crate) fn base_url(provider: &str, default: &str) -> String {
+    settings(provider)
+        .and_then(|settings| settings.base_url)
+        .unwrap_or(default.to_string())
+}
+
+/// The default headers of the current client
+pub(crate) fn headers(provider: &str) -> Result<HeaderMap> {
+    settings(provider).map_or(Ok(HeaderMap::new()), |settings| settings.header_map())
+}
+
+/// `request` with the default headers of the current client which it does not set itself
+///
+/// They are added per request rather than to the pooled HTTP clients, which are shared by every
+/// `GoldenAIClient` with the same HTTP settings
+pub(crate) fn with_headers(provider: &str, request: RequestBuilder) -> Result<RequestBuilder> {
+    let headers = headers(provider)?;
+    if headers.is_empty() {
+        return Ok(request);
+    }
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    for (name, value) in &headers {
+        if !request.headers().contains_key(name) {
+            request.headers_mut().insert(name, value.clone());
+        }
+    }
+    Ok(RequestBuilder::from_parts(client, request))
+}
+
+/// The HTTP settings of the current client, of `provider` or else of the client
+pub(crate) fn http(provider: &str) -> Option<HttpSettings> {
+    let client = current()?;
+    client
+        .provider(provider)
+        .and_then(|settings| settings.http.clone())
+        .or_else(|| client.http.clone())
+}
+
+/// The retry policy of the current client
+pub(crate) fn retry() -> Option<RetryPolicy> {
+    current()?.retry.clone()
+}
+
+#[test]
+fn test_client_settings() {
+    let client = GoldenAIClient {
+        anthropic: Some(ProviderSettings {
+            api_key: Some("sk-ant-tenant".to_string()),
+            base_url: Some("https://gateway.internal/anthropic/v1".to_string()),
+            headers: BTreeMap::from([("x-tenant".to_string(), "42".to_string())]),
+            http: None,
+        }),
+        http: Some(HttpSettings {
+            timeout: Some(30.0),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    assert!(current().is_none());
+    client.sync_scope(|| {
+        assert_eq!(
+            api_key("anthropic", "ANTHROPIC_API_KEY").unwrap(),
+            "sk-ant-tenant"
+        );
+        assert_eq!(
+            base_url("anthropic", crate::ANTHROPIC_API_URL),
+            "https://gateway.internal/anthropic/v1"
+        );
+        assert_eq!(
+            base_url("mistral", crate::MISTRAL_API_URL),
+            crate::MISTRAL_API_URL
+        );
+        assert_eq!(headers("anthropic").unwrap()["x-tenant"], "42");
+        assert!(headers("openai").unwrap().is_empty());
+        let http_client = reqwest::Client::new();
+        let request = with_headers(
+            "anthropic",
+            http_client
+                .post("https://gateway.internal/anthropic/v1/messages")
+                .header("x-api-key", "sk-ant-tenant"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        assert_eq!(request.headers()["x-tenant"], "42");
+        assert_eq!(request.headers()["x-api-key"], "sk-ant-tenant");
+        let request = with_headers(
+            "anthropic",
+            http_client
+                .post("https://gateway.internal/anthropic/v1/messages")
+                .header("x-tenant", "7"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        assert_eq!(request.headers().get_all("x-tenant").iter().count(), 1);
+        assert_eq!(request.headers()["x-tenant"], "7");
+        assert_eq!(http("gemini").unwrap().timeout, Some(30.0));
+        assert_eq!(crate::http::settings("anthropic").timeout, Some(30.0));
+    });
+
+    let error = api_key("gemini", "GOLDENAI_TEST_UNSET_API_KEY").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "GOLDENAI_TEST_UNSET_API_KEY environment variable must be set"
+    );
+    assert!(!format!("{:?}", client).contains("sk-ant-tenant"));
+
+    // settings that would be ignored are rejected
+    let settings = |api_key: Option<&str>, base_url: Option<&str>| {
+        Some(ProviderSettings {
+            api_key: api_key.map(|key| key.to_string()),
+            base_url: base_url.map(|url| url.to_string()),
+            ..Default::default()
+        })
+    };
+    let new = |ollama, openai_compat| {
+        GoldenAIClient::new(
+            None,
+            None,
+            None,
+            None,
+            ollama,
+            openai_compat,
+            None,
+            None,
+            None,
+        )
+    };
+    assert!(new(settings(None, Some("http://gpu-1:11434")), None).is_err());
+    assert!(new(settings(Some("key"), None), None).is_err());
+    assert!(new(None, settings(None, Some("http://vllm:8000/v1"))).is_err());
+    assert!(new(None, settings(Some("key"), None)).is_ok());
+}
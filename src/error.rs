@@ -0,0 +1,439 @@
+use anyhow::Result;
+use pyo3::exceptions::{PyException, PyTimeoutError};
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{IntoPyDict, PyType};
+use pyo3::{PyTypeInfo, create_exception};
+use reqwest::header::HeaderMap;
+use reqwest::{Response, StatusCode};
+use serde_json::Value;
+
+use crate::retry::retry_after;
+
+create_exception!(
+    goldenai,
+    GoldenAIError,
+    PyException,
+    "The base class of every error of a request"
+);
+create_exception!(
+    goldenai,
+    AuthenticationError,
+    GoldenAIError,
+    "The API key is missing, invalid or lacks the permission"
+);
+create_exception!(
+    goldenai,
+    RateLimitError,
+    GoldenAIError,
+    "Too many requests or tokens, or the quota is used up"
+);
+create_exception!(
+    goldenai,
+    ContextLengthError,
+    GoldenAIError,
+    "The prompt does not fit into the context window of the model"
+);
+create_exception!(
+    goldenai,
+    InvalidRequestError,
+    GoldenAIError,
+    "The request was rejected by the provider, or is not valid before it is sent"
+);
+create_exception!(
+    goldenai,
+    ProviderUnavailableError,
+    GoldenAIError,
+    "The provider is overloaded, failing or cannot be reached"
+);
+create_exception!(
+    goldenai,
+    ContentFilterError,
+    GoldenAIError,
+    "The prompt or the response was blocked by the safety filters of the provider"
+);
+create_exception!(
+    goldenai,
+    OutputValidationError,
+    GoldenAIError,
+    "The structured output does not follow the schema, also after asking the model again"
+);
+
+/// The request timed out
+///
+/// It is not named TimeoutError, which `from goldenai import *` would shadow the builtin with, but it
+/// subclasses the builtin so that `except TimeoutError` catches it as well. create_exception! takes
+/// a single base class, so the type is created with `type` instead
+#[repr(transparent)]
+pub struct RequestTimeoutError(PyAny);
+
+unsafe impl PyTypeInfo for RequestTimeoutError {
+    const NAME: &'static str = "RequestTimeoutError";
+    const MODULE: Option<&'static str> = Some("goldenai");
+
+    fn type_object_raw(py: Python<'_>) -> *mut pyo3::ffi::PyTypeObject {
+        static TYPE_OBJECT: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+        TYPE_OBJECT
+            .get_or_init(py, || {
+                let bases = (
+                    py.get_type::<GoldenAIError>(),
+                    py.get_type::<PyTimeoutError>(),
+                );
+                let namespace = [
+                    ("__doc__", "The request timed out"),
+                    ("__module__", "goldenai"),
+                ]
+                .into_py_dict(py)
+                .expect("Failed to initialize new exception type.");
+                PyType::type_object(py)
+                    .call1((Self::NAME, bases, namespace))
+                    .and_then(|new_type| Ok(new_type.downcast_into::<PyType>()?.unbind()))
+                    .expect("Failed to initialize new exception type.")
+            })
+            .as_ptr() as *mut pyo3::ffi::PyTypeObject
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Authentication,
+    RateLimit,
+    ContextLength,
+    InvalidRequest,
+    ProviderUnavailable,
+    ContentFilter,
+    Timeout,
+    Other,
+}
+
+// markers in the error messages of the providers, the status code alone does not tell them apart
+const CONTEXT_LENGTH: &[&str] = &[
+    "context_length_exceeded",
+    "maximum context length",
+    "prompt is too long",
+    "exceeds the maximum number of tokens",
+    "too large for model with",
+    "exceeds the context window",
+];
+const CONTENT_FILTER: &[&str] = &[
+    "content_filter",
+    "content_policy_violation",
+    "responsibleaipolicyviolation",
+];
+
+// the ids of Anthropic, OpenAI, Mistral and Google
+const REQUEST_ID_HEADERS: &[&str] = &[
+    "request-id",
+    "x-request-id",
+    "mistral-correlation-id",
+    "x-goog-request-id",
+];
+
+/// An error answered by a provider, either as HTTP status or as error event of a stream
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub kind: ErrorKind,
+    pub provider: &'static str, // "openai", "azure_openai", "anthropic", "ollama", "gemini", "mistral" or "openai_compat"
+    pub status_code: Option<u16>,
+    pub request_id: Option<String>,
+    pub retry_after: Option<f64>, // in seconds
+    pub body: Value,              // the parsed error of the provider, a string if it is not JSON
+    message: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    pub(crate) fn from_http(
+        provider: &'static str,
+        status: StatusCode,
+        headers: &HeaderMap,
+        text: &str,
+    ) -> Self {
+        let body = serde_json::from_str(text).unwrap_or(Value::String(text.to_string()));
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        let retry_after = retry_after(headers, &body);
+        let request_id = REQUEST_ID_HEADERS
+            .iter()
+            .find_map(|name| header(name))
+            .or(body["request_id"].as_str())
+            .map(|id| id.to_string());
+
+        Self {
+            kind: classify(Some(status.as_u16()), &body),
+            provider,
+            status_code: Some(status.as_u16()),
+            request_id,
+            retry_after,
+            message: format!("Error: HTTP {}, Response: {}", status, text),
+            body,
+        }
+    }
+
+    /// An error within a response that was successful as far as HTTP is concerned, e.g. an
+    /// error event of a stream
+    pub(crate) fn from_body(provider: &'static str, body: Value) -> Self {
+        Self {
+            kind: classify(None, &body),
+            provider,
+            status_code: None,
+            request_id: body["request_id"].as_str().map(|id| id.to_string()),
+            retry_after: retry_after(&HeaderMap::new(), &body),
+            message: format!("Error: {}", body),
+            body,
+        }
+    }
+
+    pub(crate) fn with_message(mut self, message: String) -> Self {
+        self.message = message;
+        self
+    }
+
+    pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+/// An error found before the request is sent, e.g. a missing API key
+#[derive(Debug)]
+pub struct LocalError {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl std::fmt::Display for LocalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for LocalError {}
+
+impl LocalError {
+    pub(crate) fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// Hand back a successful response, or the error of the provider
+pub(crate) async fn check_response(provider: &'static str, response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let headers = response.headers().clone();
+    let text = response.text().await?;
+    Err(ApiError::from_http(provider, status, &headers, &text).into())
+}
+
+fn classify(status: Option<u16>, body: &Value) -> ErrorKind {
+    let text = body.to_string().to_lowercase();
+    let is_marked = |markers: &[&str]| markers.iter().any(|marker| text.contains(marker));
+    match status {
+        Some(429) => ErrorKind::RateLimit,
+        Some(500..=599) => ErrorKind::ProviderUnavailable,
+        Some(401 | 403) => ErrorKind::Authentication,
+        Some(408) => ErrorKind::Timeout,
+        _ if is_marked(CONTEXT_LENGTH) => ErrorKind::ContextLength,
+        _ if is_marked(CONTENT_FILTER) => ErrorKind::ContentFilter,
+        Some(400..=499) => ErrorKind::InvalidRequest,
+        _ => classify_type(body),
+    }
+}
+
+/// The type of an error event, as named by Anthropic, OpenAI or Google
+fn classify_type(body: &Value) -> ErrorKind {
+    let error = body.get("error").unwrap_or(body);
+    let error_type = ["type", "code", "status"]
+        .iter()
+        .find_map(|field| error[field].as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match error_type.as_str() {
+        "authentication_error"
+        | "permission_error"
+        | "invalid_api_key"
+        | "unauthenticated"
+        | "permission_denied" => ErrorKind::Authentication,
+        "rate_limit_error"
+        | "rate_limit_exceeded"
+        | "insufficient_quota"
+        | "resource_exhausted" => ErrorKind::RateLimit,
+        "overloaded_error" | "api_error" | "server_error" | "unavailable" | "internal" => {
+            ErrorKind::ProviderUnavailable
+        }
+        "timeout_error" | "deadline_exceeded" => ErrorKind::Timeout,
+        "invalid_request_error" | "not_found_error" | "invalid_argument" | "not_found" => {
+            ErrorKind::InvalidRequest
+        }
+        _ => ErrorKind::Other,
+    }
+}
+
+/// The Python exception of an error, the attributes are None unless a provider answered
+pub(crate) fn to_py_err(error: anyhow::Error) -> PyErr {
+    let api_error = error.downcast_ref::<ApiError>();
+    let kind = match (api_error, error.downcast_ref::<LocalError>()) {
+        (Some(api_error), _) => api_error.kind,
+        (None, Some(local_error)) => local_error.kind,
+        (None, None) => match error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        {
+            Some(e) if e.is_timeout() => ErrorKind::Timeout,
+            Some(e) if e.is_connect() => ErrorKind::ProviderUnavailable,
+            _ => ErrorKind::Other,
+        },
+    };
+
+    Python::attach(|py| {
+        let exception_type = match kind {
+            ErrorKind::Authentication => AuthenticationError::type_object(py),
+            ErrorKind::RateLimit => RateLimitError::type_object(py),
+            ErrorKind::ContextLength => ContextLengthError::type_object(py),
+            ErrorKind::InvalidRequest => InvalidRequestError::type_object(py),
+            ErrorKind::ProviderUnavailable => ProviderUnavailableError::type_object(py),
+            ErrorKind::ContentFilter => ContentFilterError::type_object(py),
+            ErrorKind::Timeout => RequestTimeoutError::type_object(py),
+            ErrorKind::Other => GoldenAIError::type_object(py),
+        };
+        with_attributes(py, exception_type, error.to_string(), api_error)
+    })
+}
+
+/// An error of goldenai itself rather than of a provider, the attributes are None
+pub(crate) fn new_err<T: PyTypeInfo>(message: impl Into<String>) -> PyErr {
+    Python::attach(|py| with_attributes(py, T::type_object(py), message.into(), None))
+}
+
+fn with_attributes(
+    py: Python<'_>,
+    exception_type: Bound<'_, PyType>,
+    message: String,
+    api_error: Option<&ApiError>,
+) -> PyErr {
+    let err = PyErr::from_type(exception_type, message);
+    let value = err.value(py);
+    let attributes = || -> PyResult<()> {
+        value.setattr("status_code", api_error.and_then(|e| e.status_code))?;
+        value.setattr("provider", api_error.map(|e| e.provider))?;
+        value.setattr("request_id", api_error.and_then(|e| e.request_id.clone()))?;
+        value.setattr("retry_after", api_error.and_then(|e| e.retry_after))?;
+        let body = match api_error {
+            Some(api_error) => Some(pythonize::pythonize(py, &api_error.body)?),
+            None => None,
+        };
+        value.setattr("body", body)?;
+        Ok(())
+    };
+    match attributes() {
+        Ok(()) => err,
+        Err(e) => e,
+    }
+}
+
+pub(crate) fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("GoldenAIError", py.get_type::<GoldenAIError>())?;
+    m.add("AuthenticationError", py.get_type::<AuthenticationError>())?;
+    m.add("RateLimitError", py.get_type::<RateLimitError>())?;
+    m.add("ContextLengthError", py.get_type::<ContextLengthError>())?;
+    m.add("InvalidRequestError", py.get_type::<InvalidRequestError>())?;
+    m.add(
+        "ProviderUnavailableError",
+        py.get_type::<ProviderUnavailableError>(),
+    )?;
+    m.add("ContentFilterError", py.get_type::<ContentFilterError>())?;
+    m.add("RequestTimeoutError", py.get_type::<RequestTimeoutError>())?;
+    m.add(
+        "OutputValidationError",
+        py.get_type::<OutputValidationError>(),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_api_error() {
+    let mut headers = HeaderMap::new();
+    headers.insert("retry-after", "20".parse().unwrap());
+    headers.insert("request-id", "req_1".parse().unwrap());
+    let error = ApiError::from_http(
+        "anthropic",
+        StatusCode::TOO_MANY_REQUESTS,
+        &headers,
+        r#"{"type":"error","error":{"type":"rate_limit_error","message":"Number of request tokens has exceeded your per-minute rate limit"}}"#,
+    );
+    assert_eq!(error.kind, ErrorKind::RateLimit);
+    assert_eq!(error.retry_after, Some(20.0));
+    assert_eq!(error.request_id.as_deref(), Some("req_1"));
+    assert_eq!(error.body["error"]["type"], "rate_limit_error");
+    assert!(
+        error
+            .to_string()
+            .starts_with("Error: HTTP 429 Too Many Requests")
+    );
+
+    let error = ApiError::from_http(
+        "openai",
+        StatusCode::BAD_REQUEST,
+        &HeaderMap::new(),
+        r#"{"error":{"message":"Your input exceeds the context window of this model.","type":"invalid_request_error","code":"context_length_exceeded"}}"#,
+    );
+    assert_eq!(error.kind, ErrorKind::ContextLength);
+
+    let error = ApiError::from_http(
+        "gemini",
+        StatusCode::TOO_MANY_REQUESTS,
+        &HeaderMap::new(),
+        r#"{"error":{"code":429,"status":"RESOURCE_EXHAUSTED","details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"31s"}]}}"#,
+    );
+    assert_eq!(error.retry_after, Some(31.0));
+
+    let error = ApiError::from_http(
+        "ollama",
+        StatusCode::NOT_FOUND,
+        &HeaderMap::new(),
+        "404 page not found",
+    );
+    assert_eq!(error.kind, ErrorKind::InvalidRequest);
+    assert_eq!(error.body, "404 page not found");
+
+    let error = ApiError::from_body(
+        "anthropic",
+        serde_json::json!({"type": "overloaded_error", "message": "Overloaded"}),
+    );
+    assert_eq!(error.kind, ErrorKind::ProviderUnavailable);
+
+    Python::initialize();
+    let error = LocalError::new(
+        ErrorKind::Authentication,
+        "OPENAI_API_KEY environment variable must be set",
+    );
+    Python::attach(|py| {
+        let err = to_py_err(anyhow::Error::new(error));
+        assert!(err.is_instance_of::<AuthenticationError>(py));
+        assert!(err.value(py).getattr("status_code").unwrap().is_none());
+    });
+
+    Python::attach(|py| {
+        let err = to_py_err(anyhow::Error::new(LocalError::new(
+            ErrorKind::Timeout,
+            "timed out",
+        )));
+        assert!(err.is_instance_of::<RequestTimeoutError>(py));
+        assert!(err.is_instance_of::<PyTimeoutError>(py));
+        assert!(err.is_instance_of::<GoldenAIError>(py));
+        assert!(err.value(py).getattr("provider").unwrap().is_none());
+    });
+}
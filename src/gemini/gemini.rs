@@ -0,0 +1,90 @@
+use anyhow::Result;
+use reqwest::Response;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::GEMINI_API_URL;
+use crate::error::{ApiError, ErrorKind};
+use crate::gemini::structs::{GeminiRequest, GeminiResponse, GenerateContentRequest};
+use crate::response::LLMResponse;
+use crate::retry::send_with_retry;
+use crate::runtime::{client, runtime};
+
+pub fn get_response_gemini(request_body: GeminiRequest) -> Result<LLMResponse> {
+    runtime().block_on(request_gemini(request_body))
+}
+
+pub fn get_count_tokens_gemini(request_body: GeminiRequest) -> Result<u32> {
+    runtime().block_on(count_tokens_gemini(request_body))
+}
+
+pub(crate) async fn request_gemini(request_body: GeminiRequest) -> Result<LLMResponse> {
+    let response = post_gemini(&request_body, "generateContent").await?;
+    let response: GeminiResponse = response.json().await?;
+    if response.candidates.is_empty() {
+        // the prompt itself was blocked, e.g. by the safety filters
+        let feedback = response.prompt_feedback.unwrap_or_default();
+        let message = format!(
+            "Error: no candidates in response, prompt feedback: {}",
+            feedback
+        );
+        return Err(ApiError::from_body("gemini", feedback)
+            .with_kind(ErrorKind::ContentFilter)
+            .with_message(message)
+            .into());
+    }
+    Ok(response.to_llm_response())
+}
+
+/// Send the request to `streamGenerateContent` and hand back the raw SSE response
+pub(crate) async fn request_gemini_stream(request_body: GeminiRequest) -> Result<Response> {
+    post_gemini(&request_body, "streamGenerateContent?alt=sse").await
+}
+
+pub(crate) async fn count_tokens_gemini(request_body: GeminiRequest) -> Result<u32> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct CountTokensResponse {
+        total_tokens: u32,
+    }
+
+    let mut body =
+        serde_json::to_value(GenerateContentRequest::from_gemini_request(&request_body)?)?;
+    // the system instruction and tools are only counted as part of a whole generateContentRequest
+    body["model"] = json!(format!("models/{}", request_body.model.to_str()));
+    let response = send_gemini(
+        &request_body,
+        "countTokens",
+        &json!({"generateContentRequest": body}),
+    )
+    .await?;
+    let response: CountTokensResponse = response.json().await?;
+    Ok(response.total_tokens)
+}
+
+async fn post_gemini(request_body: &GeminiRequest, method: &str) -> Result<Response> {
+    let body = serde_json::to_value(GenerateContentRequest::from_gemini_request(request_body)?)?;
+    send_gemini(request_body, method, &body).await
+}
+
+async fn send_gemini(
+    request_body: &GeminiRequest,
+    method: &str,
+    body: &serde_json::Value,
+) -> Result<Response> {
+    let api_key = crate::client::api_key("gemini", "GEMINI_API_KEY")?;
+
+    let url = format!(
+        "{}/models/{}:{}",
+        crate::client::base_url("gemini", GEMINI_API_URL),
+        request_body.model.to_str(),
+        method
+    );
+    let request = client("gemini", &url)?
+        .post(&url)
+        .header("x-goog-api-key", api_key)
+        .header("content-type", "application/json")
+        .json(body);
+
+    send_with_retry("gemini", request).await
+}
@@ -0,0 +1,385 @@
+use anyhow::Result;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+use crate::SupportedModels;
+use crate::anthropic::structs::ResponseAnthropic;
+use crate::generation::GenerationConfig;
+use crate::message::{Content, ContentTypeInner, Message, TextContent};
+use crate::response::{LLMResponse, Usage};
+use crate::schema::Json;
+use crate::tools::{Tool, ToolCall, gemini_tool_config};
+
+#[derive(Serialize, Clone, Debug)]
+#[pyclass(dict, get_all, set_all, subclass)]
+pub struct GeminiRequest {
+    pub(crate) model: SupportedModels,
+    pub(crate) system: Option<String>,
+    pub(crate) messages: Vec<Message>,
+    pub(crate) max_output_tokens: Option<u32>,
+    pub(crate) tools: Vec<Tool>,
+    pub(crate) tool_choice: Option<String>,
+    pub(crate) format: Option<Json>, // structured output
+    pub(crate) generation_config: Option<GenerationConfig>,
+}
+
+#[pymethods]
+impl GeminiRequest {
+    #[new]
+    #[pyo3(signature = (model, messages, prompt=None, max_output_tokens=None, tools=None, tool_choice=None, generation_config=None))]
+    pub fn new(
+        model: &str,
+        messages: Vec<Message>,
+        prompt: Option<&str>,
+        max_output_tokens: Option<u32>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<&str>,
+        generation_config: Option<GenerationConfig>,
+    ) -> PyResult<Self> {
+        if let Some(config) = &generation_config {
+            config
+                .to_gemini()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+        Ok(Self {
+            model: SupportedModels::from_str(model)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?,
+            system: prompt.map(|s| s.to_string()),
+            messages,
+            max_output_tokens,
+            tools: tools.unwrap_or_default(),
+            tool_choice: tool_choice.map(|s| s.to_string()),
+            format: None,
+            generation_config,
+        })
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("{self:?}"))
+    }
+
+    pub fn add_response(&mut self, response: LLMResponse) {
+        let mut content: Vec<Content> = response
+            .content
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|block| !block.text.is_empty())
+            .map(|block| Content {
+                ctx: ContentTypeInner::Text(TextContent {
+                    content_type: "text".to_string(),
+                    text: block.text,
+                }),
+                cache_control: None,
+            })
+            .collect();
+        content.extend(response.tool_calls.into_iter().map(|call| Content {
+            ctx: ContentTypeInner::ToolCall(call),
+            cache_control: None,
+        }));
+
+        self.messages.push(Message {
+            role: "assistant".to_string(),
+            content,
+        });
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GeminiContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>, // "user" or "model"
+    #[serde(default)]
+    pub parts: Vec<Value>,
+}
+
+impl GeminiContent {
+    fn from_message(message: &Message) -> Self {
+        let parts = message
+            .content
+            .iter()
+            .filter_map(|each_content| match &each_content.ctx {
+                ContentTypeInner::Text(text) => Some(json!({"text": text.text})),
+                ContentTypeInner::Document(doc) => Some(doc.to_gemini()),
+                ContentTypeInner::ToolCall(call) => Some(call.to_gemini()),
+                ContentTypeInner::ToolResult(result) => Some(json!({
+                    "functionResponse": {
+                        // the name is required by Gemini, see `Content.from_tool_result`
+                        "name": result.name.as_ref().unwrap_or(&result.tool_call_id),
+                        "response": match result.is_error {
+                            true => json!({"error": result.content}),
+                            false => json!({"result": result.content}),
+                        },
+                    }
+                })),
+                ContentTypeInner::Thinking(_) => None,
+            })
+            .collect();
+
+        Self {
+            role: Some(match message.role.as_str() {
+                "assistant" | "model" => "model".to_string(),
+                _ => "user".to_string(),
+            }),
+            parts,
+        }
+    }
+
+    pub(crate) fn text(&self) -> String {
+        self.parts
+            .iter()
+            .filter(|part| part["thought"] != true)
+            .filter_map(|part| part["text"].as_str())
+            .collect()
+    }
+
+    /// Gemini does not always assign ids to function calls, `first_index` keeps the generated
+    /// ones unique over the chunks of a stream
+    pub(crate) fn to_tool_calls(&self, first_index: usize) -> Vec<ToolCall> {
+        self.parts
+            .iter()
+            .filter_map(|part| part.get("functionCall"))
+            .enumerate()
+            .filter_map(|(index, call)| {
+                Some(ToolCall {
+                    id: call["id"]
+                        .as_str()
+                        .map_or(format!("call_{}", first_index + index), |id| id.to_string()),
+                    name: call["name"].as_str()?.to_string(),
+                    arguments: call.get("args").cloned().unwrap_or(json!({})),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Body of `generateContent` (and `streamGenerateContent`)
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateContentRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiContent>,
+    pub contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<Value>,
+}
+
+impl GenerateContentRequest {
+    pub fn from_gemini_request(request_body: &GeminiRequest) -> Result<Self> {
+        let mut generation_config = match &request_body.generation_config {
+            Some(config) => config.to_gemini()?,
+            None => serde_json::Map::new(),
+        };
+        if let Some(max_output_tokens) = request_body.max_output_tokens {
+            generation_config.insert("maxOutputTokens".to_string(), json!(max_output_tokens));
+        }
+        if let Some(format) = &request_body.format {
+            generation_config.insert("responseMimeType".to_string(), json!("application/json"));
+            generation_config.insert("responseJsonSchema".to_string(), format.0.clone());
+        }
+
+        Ok(Self {
+            system_instruction: request_body.system.as_ref().map(|system| GeminiContent {
+                role: None,
+                parts: vec![json!({"text": system})],
+            }),
+            contents: request_body
+                .messages
+                .iter()
+                .map(GeminiContent::from_message)
+                .collect(),
+            tools: match request_body.tools.is_empty() {
+                true => vec![],
+                false => vec![json!({
+                    "functionDeclarations": request_body.tools.iter().map(Tool::to_gemini).collect::<Vec<Value>>()
+                })],
+            },
+            tool_config: gemini_tool_config(&request_body.tool_choice),
+            generation_config: (!generation_config.is_empty())
+                .then_some(Value::Object(generation_config)),
+        })
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiUsage {
+    #[serde(default)]
+    pub prompt_token_count: u32,
+    #[serde(default)]
+    pub candidates_token_count: u32,
+    #[serde(default)]
+    pub thoughts_token_count: u32,
+    #[serde(default)]
+    pub cached_content_token_count: u32, // part of prompt_token_count
+    #[serde(default)]
+    pub tool_use_prompt_token_count: u32,
+    #[serde(default)]
+    pub prompt_tokens_details: Vec<GeminiModalityCount>,
+    #[serde(default)]
+    pub candidates_tokens_details: Vec<GeminiModalityCount>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiModalityCount {
+    pub modality: String, // "TEXT", "IMAGE", "AUDIO", "VIDEO" or "DOCUMENT"
+    #[serde(default)]
+    pub token_count: u32,
+}
+
+impl GeminiUsage {
+    pub(crate) fn to_usage(&self) -> Usage {
+        let mut details = BTreeMap::new();
+        for (prefix, counts) in [
+            ("input", &self.prompt_tokens_details),
+            ("output", &self.candidates_tokens_details),
+        ] {
+            for count in counts {
+                let name = format!("{}_{}_tokens", prefix, count.modality.to_lowercase());
+                *details.entry(name).or_default() += count.token_count;
+            }
+        }
+        if self.tool_use_prompt_token_count > 0 {
+            details.insert(
+                "tool_use_prompt_tokens".to_string(),
+                self.tool_use_prompt_token_count,
+            );
+        }
+
+        Usage {
+            input_tokens: self.prompt_token_count,
+            // thinking is billed as output
+            output_tokens: self.candidates_token_count + self.thoughts_token_count,
+            reasoning_tokens: self.thoughts_token_count,
+            cache_creation_input_tokens: 0,
+            cache_read_input_tokens: self.cached_content_token_count,
+            details,
+            durations: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidate {
+    #[serde(default)]
+    pub content: GeminiContent,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiResponse {
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+    #[serde(default)]
+    pub usage_metadata: GeminiUsage,
+    pub model_version: SupportedModels,
+    #[serde(default)]
+    pub response_id: String,
+    pub prompt_feedback: Option<Value>,
+}
+
+impl GeminiResponse {
+    pub fn to_llm_response(self) -> LLMResponse {
+        let candidate = self.candidates.into_iter().next();
+        let content = candidate
+            .as_ref()
+            .map(|candidate| candidate.content.clone())
+            .unwrap_or_default();
+
+        LLMResponse {
+            id: self.response_id,
+            model: self.model_version,
+            response_type: "generateContent".to_string(),
+            usage: self.usage_metadata.to_usage(),
+            role: Some("model".to_string()),
+            content: Some(vec![ResponseAnthropic {
+                content_type: "text".to_string(),
+                text: content.text(),
+            }]),
+            stop_reason: candidate.and_then(|candidate| candidate.finish_reason),
+            tool_calls: content.to_tool_calls(0),
+            ..Default::default()
+        }
+    }
+}
+
+#[test]
+fn test_generate_content_request() {
+    let request = GeminiRequest {
+        model: SupportedModels::Gemini25Flash,
+        system: Some("Be brief".to_string()),
+        messages: vec![
+            Message {
+                role: "user".to_string(),
+                content: vec![Content {
+                    ctx: ContentTypeInner::Text(TextContent {
+                        content_type: "text".to_string(),
+                        text: "Weather in Berlin?".to_string(),
+                    }),
+                    cache_control: None,
+                }],
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: vec![Content {
+                    ctx: ContentTypeInner::ToolCall(ToolCall {
+                        id: "call_0".to_string(),
+                        name: "get_weather".to_string(),
+                        arguments: json!({"city": "Berlin"}),
+                    }),
+                    cache_control: None,
+                }],
+            },
+        ],
+        max_output_tokens: Some(100),
+        tools: vec![],
+        tool_choice: None,
+        format: None,
+        generation_config: Some(GenerationConfig {
+            temperature: Some(0.5),
+            ..Default::default()
+        }),
+    };
+
+    let body = serde_json::to_value(GenerateContentRequest::from_gemini_request(&request).unwrap())
+        .unwrap();
+    assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief");
+    assert_eq!(body["contents"][1]["role"], "model");
+    assert_eq!(
+        body["contents"][1]["parts"][0]["functionCall"]["args"]["city"],
+        "Berlin"
+    );
+    assert_eq!(body["generationConfig"]["maxOutputTokens"], 100);
+    assert_eq!(body["generationConfig"]["temperature"], 0.5);
+    assert!(body.get("tools").is_none());
+
+    let response: GeminiResponse = serde_json::from_value(json!({
+        "candidates": [{
+            "content": {"role": "model", "parts": [{"text": "Sunny"}, {"functionCall": {"name": "get_weather", "args": {}}}]},
+            "finishReason": "STOP",
+        }],
+        "usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 3, "thoughtsTokenCount": 2,
+            "promptTokensDetails": [{"modality": "TEXT", "tokenCount": 4}, {"modality": "IMAGE", "tokenCount": 6}]},
+        "modelVersion": "gemini-2.5-flash",
+        "responseId": "abc",
+    }))
+    .unwrap();
+    let response = response.to_llm_response();
+    assert_eq!(response.to_string(), "Sunny");
+    assert_eq!(response.usage.output_tokens, 5);
+    assert_eq!(response.usage.reasoning_tokens, 2);
+    assert_eq!(response.usage.details["input_image_tokens"], 6);
+    assert_eq!(response.tool_calls[0].id, "call_0");
+}
@@ -0,0 +1,298 @@
+use anyhow::{Result, anyhow};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::SupportedModels;
+
+/// Sampling and generation parameters, described provider-neutrally and mapped to the field
+/// names of each provider when the request is sent
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[pyclass(dict, get_all, frozen)]
+pub struct GenerationConfig {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u32>,
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+    pub presence_penalty: Option<f64>,
+    pub frequency_penalty: Option<f64>,
+    pub num_ctx: Option<u32>,        // Ollama only
+    pub num_predict: Option<i32>,    // Ollama only, -1 for no limit
+    pub repeat_penalty: Option<f64>, // Ollama only
+}
+
+// (name of GenerationConfig, name of the provider)
+const OPENAI: &[(&str, &str)] = &[("temperature", "temperature"), ("top_p", "top_p")];
+const ANTHROPIC: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("top_k", "top_k"),
+    ("stop", "stop_sequences"),
+];
+const GEMINI: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "topP"),
+    ("top_k", "topK"),
+    ("stop", "stopSequences"),
+    ("seed", "seed"),
+    ("presence_penalty", "presencePenalty"),
+    ("frequency_penalty", "frequencyPenalty"),
+];
+const MISTRAL: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("stop", "stop"),
+    ("seed", "random_seed"),
+    ("presence_penalty", "presence_penalty"),
+    ("frequency_penalty", "frequency_penalty"),
+];
+// vLLM, llama.cpp and most other servers take top_k as well
+const CHAT_COMPLETIONS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("top_k", "top_k"),
+    ("stop", "stop"),
+    ("seed", "seed"),
+    ("presence_penalty", "presence_penalty"),
+    ("frequency_penalty", "frequency_penalty"),
+];
+const OLLAMA: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("top_k", "top_k"),
+    ("stop", "stop"),
+    ("seed", "seed"),
+    ("presence_penalty", "presence_penalty"),
+    ("frequency_penalty", "frequency_penalty"),
+    ("num_ctx", "num_ctx"),
+    ("num_predict", "num_predict"),
+    ("repeat_penalty", "repeat_penalty"),
+];
+
+#[pymethods]
+impl GenerationConfig {
+    #[new]
+    #[pyo3(signature = (temperature=None, top_p=None, top_k=None, stop=None, seed=None, presence_penalty=None, frequency_penalty=None, num_ctx=None, num_predict=None, repeat_penalty=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        temperature: Option<f64>,
+        top_p: Option<f64>,
+        top_k: Option<u32>,
+        stop: Option<Vec<String>>,
+        seed: Option<i64>,
+        presence_penalty: Option<f64>,
+        frequency_penalty: Option<f64>,
+        num_ctx: Option<u32>,
+        num_predict: Option<i32>,
+        repeat_penalty: Option<f64>,
+    ) -> PyResult<Self> {
+        let config = Self {
+            temperature,
+            top_p,
+            top_k,
+            stop,
+            seed,
+            presence_penalty,
+            frequency_penalty,
+            num_ctx,
+            num_predict,
+            repeat_penalty,
+        };
+        config
+            .validate()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(config)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        let params: Vec<String> = self
+            .params()
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        Ok(format!("GenerationConfig<{}>", params.join(",")))
+    }
+}
+
+impl GenerationConfig {
+    /// The ranges every provider accepts, narrower limits are checked by the `to_*` methods
+    fn validate(&self) -> Result<()> {
+        let in_range = |name: &str, value: Option<f64>, min: f64, max: f64| match value {
+            Some(value) if !(min..=max).contains(&value) => Err(anyhow!(
+                "{} must be between {} and {}, got {}",
+                name,
+                min,
+                max,
+                value
+            )),
+            _ => Ok(()),
+        };
+        in_range("temperature", self.temperature, 0.0, 2.0)?;
+        in_range("top_p", self.top_p, 0.0, 1.0)?;
+        in_range("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        in_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        in_range("repeat_penalty", self.repeat_penalty, 0.0, f64::MAX)?;
+        if self.top_k == Some(0) {
+            return Err(anyhow!("top_k must be at least 1"));
+        }
+        if self.num_ctx == Some(0) {
+            return Err(anyhow!("num_ctx must be at least 1"));
+        }
+        if self.num_predict.is_some_and(|num_predict| num_predict < -2) {
+            return Err(anyhow!(
+                "num_predict must be -1 (no limit), -2 (fill the context) or positive"
+            ));
+        }
+        if let Some(stop) = &self.stop
+            && stop.iter().any(String::is_empty)
+        {
+            return Err(anyhow!("stop must not contain empty sequences"));
+        }
+        Ok(())
+    }
+
+    /// The parameters that are set, by their provider-neutral names
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        [
+            ("temperature", self.temperature.map(|v| json!(v))),
+            ("top_p", self.top_p.map(|v| json!(v))),
+            ("top_k", self.top_k.map(|v| json!(v))),
+            ("stop", self.stop.as_ref().map(|v| json!(v))),
+            ("seed", self.seed.map(|v| json!(v))),
+            ("presence_penalty", self.presence_penalty.map(|v| json!(v))),
+            (
+                "frequency_penalty",
+                self.frequency_penalty.map(|v| json!(v)),
+            ),
+            ("num_ctx", self.num_ctx.map(|v| json!(v))),
+            ("num_predict", self.num_predict.map(|v| json!(v))),
+            ("repeat_penalty", self.repeat_penalty.map(|v| json!(v))),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+
+    /// Rename the parameters that are set, a parameter the provider does not know is an error
+    /// rather than being dropped silently
+    fn to_fields(&self, provider: &str, fields: &[(&str, &str)]) -> Result<Map<String, Value>> {
+        let mut map = Map::new();
+        for (name, value) in self.params() {
+            match fields.iter().find(|(known, _)| *known == name) {
+                Some((_, field)) => map.insert(field.to_string(), value),
+                None => return Err(anyhow!("{} is not supported by {}", name, provider)),
+            };
+        }
+        Ok(map)
+    }
+
+    /// Top-level fields of the Responses API, reasoning models take none of them
+    pub(crate) fn to_openai(&self, model: &SupportedModels) -> Result<Map<String, Value>> {
+        let map = self.to_fields("OpenAI", OPENAI)?;
+        if let Some(name) = map.keys().next()
+            && model.info().is_some_and(|info| info.reasoning)
+        {
+            return Err(anyhow!(
+                "{} is a reasoning model and does not accept {}",
+                model.to_str(),
+                name
+            ));
+        }
+        Ok(map)
+    }
+
+    /// Top-level fields of the Messages API
+    pub(crate) fn to_anthropic(&self) -> Result<Map<String, Value>> {
+        if let Some(temperature) = self.temperature
+            && temperature > 1.0
+        {
+            return Err(anyhow!(
+                "temperature must be between 0 and 1 for Anthropic, got {}",
+                temperature
+            ));
+        }
+        self.to_fields("Anthropic", ANTHROPIC)
+    }
+
+    /// Fields of `generationConfig`
+    pub(crate) fn to_gemini(&self) -> Result<Map<String, Value>> {
+        self.to_fields("Gemini", GEMINI)
+    }
+
+    pub(crate) fn to_mistral(&self) -> Result<Map<String, Value>> {
+        self.to_fields("Mistral", MISTRAL)
+    }
+
+    /// Top-level fields of `/chat/completions` on other servers
+    pub(crate) fn to_chat_completions(&self) -> Result<Map<String, Value>> {
+        self.to_fields("Chat Completions", CHAT_COMPLETIONS)
+    }
+
+    /// Fields of `options`
+    pub(crate) fn to_ollama(&self) -> Result<Map<String, Value>> {
+        self.to_fields("Ollama", OLLAMA)
+    }
+}
+
+/// The body of a request with the top-level fields of a GenerationConfig added
+pub(crate) fn with_params(
+    body: impl Serialize,
+    params: Option<Map<String, Value>>,
+) -> Result<Value> {
+    let mut body = serde_json::to_value(body)?;
+    if let (Some(object), Some(params)) = (body.as_object_mut(), params) {
+        object.extend(params);
+    }
+    Ok(body)
+}
+
+#[test]
+fn test_generation_config() {
+    let config = GenerationConfig {
+        temperature: Some(0.2),
+        top_k: Some(40),
+        stop: Some(vec!["\n\n".to_string()]),
+        ..Default::default()
+    };
+    let anthropic = config.to_anthropic().unwrap();
+    assert_eq!(anthropic["stop_sequences"], json!(["\n\n"]));
+    assert_eq!(anthropic["top_k"], 40);
+    assert_eq!(config.to_gemini().unwrap()["topK"], 40);
+    assert_eq!(config.to_ollama().unwrap()["temperature"], 0.2);
+    assert_eq!(
+        config.to_mistral().unwrap_err().to_string(),
+        "top_k is not supported by Mistral"
+    );
+
+    let config = GenerationConfig {
+        temperature: Some(1.5),
+        seed: Some(7),
+        ..Default::default()
+    };
+    assert!(config.to_anthropic().is_err());
+    assert_eq!(config.to_mistral().unwrap()["random_seed"], 7);
+
+    let config = GenerationConfig {
+        temperature: Some(0.0),
+        ..Default::default()
+    };
+    assert!(config.to_openai(&SupportedModels::GPT41Nano).is_ok());
+    assert_eq!(
+        config
+            .to_openai(&SupportedModels::from_str("gpt-5-nano").unwrap())
+            .unwrap_err()
+            .to_string(),
+        "gpt-5-nano is a reasoning model and does not accept temperature"
+    );
+
+    assert!(
+        GenerationConfig {
+            top_p: Some(1.5),
+            ..Default::default()
+        }
+        .validate()
+        .is_err()
+    );
+}
@@ -1,32 +1,67 @@
+pub mod agent;
+pub mod client;
+pub mod error;
+pub mod generation;
+pub mod http;
 pub mod message;
+pub mod registry;
+mod request;
 pub mod response;
+pub mod retry;
+mod runtime;
+pub mod schema;
+pub mod stream;
+pub mod tools;
 
 pub mod openai {
+    pub mod azure;
+    pub mod completions;
+    #[allow(clippy::module_inception)]
     pub mod openai;
     pub mod structs;
 }
 
 pub mod anthropic {
+    #[allow(clippy::module_inception)]
     pub mod anthropic;
     pub mod structs;
 }
 
 pub mod ollama {
+    #[allow(clippy::module_inception)]
     pub mod ollama;
     pub mod structs;
 }
 
+pub mod gemini {
+    #[allow(clippy::module_inception)]
+    pub mod gemini;
+    pub mod structs;
+}
+
+pub mod mistral {
+    #[allow(clippy::module_inception)]
+    pub mod mistral;
+    pub mod structs;
+}
+
 use anyhow::{Result, anyhow};
-use pyo3::exceptions::PyException;
+use pyo3::exceptions::PyValueError;
+
+use crate::error::{InvalidRequestError, new_err, to_py_err};
+use crate::request::ProviderRequest;
 use pyo3::prelude::*;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-const OPENAI_API_URL: &str = "https://api.openai.com/v1/responses";
-const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1/messages";
+const OPENAI_API_URL: &str = "https://api.openai.com/v1";
+const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1";
+const GEMINI_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+const MISTRAL_API_URL: &str = "https://api.mistral.ai/v1";
 
-#[pyclass(eq, eq_int)]
-#[derive(PartialEq, Clone, Debug)]
+/// The known models, any other model name is kept as `Custom` and sent as is
+#[derive(PartialEq, Clone, Debug, Default)]
 pub enum SupportedModels {
+    #[default]
     GPT41Nano,
     GPT41Mini,
     GPT41,
@@ -35,6 +70,13 @@ pub enum SupportedModels {
     GPT5Nano,
     Claude35HaikuLatest,
     Qwen25VL,
+    Gemini25Pro,
+    Gemini25Flash,
+    Gemini25FlashLite,
+    MistralLargeLatest,
+    MistralMediumLatest,
+    MistralSmallLatest,
+    Custom(String),
 }
 
 impl Serialize for SupportedModels {
@@ -56,14 +98,25 @@ impl<'de> Deserialize<'de> for SupportedModels {
     }
 }
 
-impl Default for SupportedModels {
-    fn default() -> Self {
-        SupportedModels::GPT41Nano
+// handed to Python as the model name
+impl<'py> IntoPyObject<'py> for &SupportedModels {
+    type Target = pyo3::types::PyString;
+    type Output = Bound<'py, Self::Target>;
+    type Error = std::convert::Infallible;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(pyo3::types::PyString::new(py, self.to_str()))
+    }
+}
+
+impl<'py> FromPyObject<'py> for SupportedModels {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        Self::from_str(&ob.extract::<String>()?).map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
 impl SupportedModels {
-    fn to_str(&self) -> &'static str {
+    fn to_str(&self) -> &str {
         match self {
             SupportedModels::GPT41Nano => "gpt-4.1-nano",
             SupportedModels::GPT41Mini => "gpt-4.1-mini",
@@ -73,90 +126,181 @@ impl SupportedModels {
             SupportedModels::GPT5Nano => "gpt-5-nano",
             SupportedModels::Claude35HaikuLatest => "claude-3-5-haiku-latest",
             SupportedModels::Qwen25VL => "qwen2.5vl:latest",
+            SupportedModels::Gemini25Pro => "gemini-2.5-pro",
+            SupportedModels::Gemini25Flash => "gemini-2.5-flash",
+            SupportedModels::Gemini25FlashLite => "gemini-2.5-flash-lite",
+            SupportedModels::MistralLargeLatest => "mistral-large-latest",
+            SupportedModels::MistralMediumLatest => "mistral-medium-latest",
+            SupportedModels::MistralSmallLatest => "mistral-small-latest",
+            SupportedModels::Custom(model) => model,
         }
     }
 
+    /// Only an empty name is rejected, unknown models are kept as `Custom`
     fn from_str(model: &str) -> Result<SupportedModels> {
-        match model {
-            "gpt-4.1-nano-2025-04-14" => Ok(SupportedModels::GPT41Nano),
-            "gpt-4.1-nano" => Ok(SupportedModels::GPT41Nano),
-            "gpt-4.1-mini-2025-04-14" => Ok(SupportedModels::GPT41Mini),
-            "gpt-4.1-mini" => Ok(SupportedModels::GPT41Mini),
-            "gpt-4.1-2025-04-14" => Ok(SupportedModels::GPT41),
-            "gpt-4.1" => Ok(SupportedModels::GPT41),
-            "gpt-5" => Ok(SupportedModels::GPT5),
-            "gpt-5-2025-08-07" => Ok(SupportedModels::GPT5),
-            "gpt-5-mini" => Ok(SupportedModels::GPT5Mini),
-            "gpt-5-mini-2025-08-07" => Ok(SupportedModels::GPT5Mini),
-            "gpt-5-nano" => Ok(SupportedModels::GPT5Nano),
-            "gpt-5-nano-2025-08-07" => Ok(SupportedModels::GPT5Nano),
-            "claude-3-5-haiku-latest" => Ok(SupportedModels::Claude35HaikuLatest),
-            "claude-3-5-haiku-20241022" => Ok(SupportedModels::Claude35HaikuLatest),
-            "qwen2.5vl:latest" => Ok(SupportedModels::Qwen25VL),
-            "qwen2.5vl:7b" => Ok(SupportedModels::Qwen25VL),
-            "qwen2.5vl" => Ok(SupportedModels::Qwen25VL),
-            _ => Err(anyhow!("Unsupported model: {}", model)),
+        if model.is_empty() {
+            return Err(anyhow!("The model name must not be empty"));
         }
+        // aliases (e.g. dated snapshots) are sent as given, `info` resolves them
+        Ok(match model {
+            "gpt-4.1-nano" => SupportedModels::GPT41Nano,
+            "gpt-4.1-mini" => SupportedModels::GPT41Mini,
+            "gpt-4.1" => SupportedModels::GPT41,
+            "gpt-5" => SupportedModels::GPT5,
+            "gpt-5-mini" => SupportedModels::GPT5Mini,
+            "gpt-5-nano" => SupportedModels::GPT5Nano,
+            "claude-3-5-haiku-latest" => SupportedModels::Claude35HaikuLatest,
+            "qwen2.5vl:latest" => SupportedModels::Qwen25VL,
+            "gemini-2.5-pro" => SupportedModels::Gemini25Pro,
+            "gemini-2.5-flash" => SupportedModels::Gemini25Flash,
+            "gemini-2.5-flash-lite" => SupportedModels::Gemini25FlashLite,
+            "mistral-large-latest" => SupportedModels::MistralLargeLatest,
+            "mistral-medium-latest" => SupportedModels::MistralMediumLatest,
+            "mistral-small-latest" => SupportedModels::MistralSmallLatest,
+            _ => SupportedModels::Custom(model.to_string()),
+        })
+    }
+
+    /// The registry entry of the model or of the model it is an alias of, None for models that
+    /// are not in the registry
+    pub(crate) fn info(&self) -> Option<registry::ModelInfo> {
+        registry::lookup(self.to_str())
     }
 }
 
 #[pyfunction]
-fn send<'p>(request_body: Bound<'p, PyAny>) -> PyResult<response::LLMResponse> {
-    if let Ok(anthropic_req) = request_body.extract::<anthropic::structs::AnthropicRequest>() {
-        match anthropic::anthropic::get_response_anthropic(anthropic_req) {
-            Ok(response) => Ok(response),
-            Err(e) => Err(PyException::new_err(e.to_string())),
-        }
-    } else if let Ok(openai_req) = request_body.extract::<openai::structs::OpenAIRequest>() {
-        match openai::openai::get_response_openai(openai_req) {
-            Ok(response) => Ok(response),
-            Err(e) => Err(PyException::new_err(e.to_string())),
-        }
-    } else if let Ok(ollama_req) = request_body.extract::<ollama::structs::OllamaRequest>() {
-        match ollama::ollama::get_response_ollama(ollama_req, false) {
+#[pyo3(signature = (request_body, schema=None, max_reasks=0, http=None))]
+fn send<'p>(
+    py: Python<'p>,
+    request_body: Bound<'p, PyAny>,
+    schema: Option<Bound<'p, PyAny>>,
+    max_reasks: u32,
+    http: Option<http::HttpSettings>,
+) -> PyResult<response::LLMResponse> {
+    http::sync_scope(http, || {
+        if let Some(schema) = schema {
+            schema::send_structured(py, &request_body, &schema, max_reasks)
+        } else if max_reasks > 0 {
+            Err(PyValueError::new_err(
+                "max_reasks is only supported with a schema",
+            ))
+        } else {
+            let request = ProviderRequest::extract(&request_body)?;
             // NOTE! in send mode, chat mode is disabled
-            Ok(response) => Ok(response),
-            Err(e) => Err(PyException::new_err(e.to_string())),
+            py.detach(|| request.send(false)).map_err(to_py_err)
         }
-    } else {
-        Err(PyException::new_err("Invalid request body"))
-    }
+    })
 }
 
 #[pyfunction]
-fn chat(request_body: Bound<PyAny>) -> PyResult<response::LLMResponse> {
-    if let Ok(ollama_req) = request_body.extract::<ollama::structs::OllamaRequest>() {
-        match ollama::ollama::get_response_ollama(ollama_req, true) {
-            // NOTE! in send mode, chat mode is disabled
-            Ok(response) => Ok(response),
-            Err(e) => Err(PyException::new_err(e.to_string())),
-        }
-    } else {
-        Err(PyException::new_err(
+#[pyo3(signature = (request_body, http=None))]
+fn chat(
+    py: Python<'_>,
+    request_body: Bound<PyAny>,
+    http: Option<http::HttpSettings>,
+) -> PyResult<response::LLMResponse> {
+    http::sync_scope(http, || match ProviderRequest::extract(&request_body)? {
+        request @ ProviderRequest::Ollama(_) => py.detach(|| request.send(true)).map_err(to_py_err),
+        _ => Err(new_err::<InvalidRequestError>(
             "Only Ollama is supported with chat mode",
-        ))
-    }
+        )),
+    })
+}
+
+#[pyfunction]
+#[pyo3(name = "stream", signature = (request_body, chat=false, http=None))]
+fn send_stream<'p>(
+    py: Python<'p>,
+    request_body: Bound<'p, PyAny>,
+    chat: bool,
+    http: Option<http::HttpSettings>,
+) -> PyResult<stream::LLMStream> {
+    http::sync_scope(http, || {
+        let request = ProviderRequest::extract(&request_body)?;
+        let format = request.stream_format(chat);
+        stream::LLMStream::start(py, format, request.request_stream(chat)).map_err(to_py_err)
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (request_body, http=None))]
+fn count_tokens<'p>(
+    py: Python<'p>,
+    request_body: Bound<'p, PyAny>,
+    http: Option<http::HttpSettings>,
+) -> PyResult<u32> {
+    http::sync_scope(http, || {
+        let request = ProviderRequest::extract(&request_body)?;
+        py.detach(|| runtime::runtime().block_on(request.count_tokens()))
+            .map_err(to_py_err)
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (request_body, http=None))]
+fn send_async<'p>(
+    py: Python<'p>,
+    request_body: Bound<'p, PyAny>,
+    http: Option<http::HttpSettings>,
+) -> PyResult<Bound<'p, PyAny>> {
+    http::sync_scope(http, || {
+        let request = ProviderRequest::extract(&request_body)?;
+        // NOTE! in send mode, chat mode is disabled
+        runtime::future_into_py(py, request.request(false))
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (request_body, http=None))]
+fn chat_async<'p>(
+    py: Python<'p>,
+    request_body: Bound<'p, PyAny>,
+    http: Option<http::HttpSettings>,
+) -> PyResult<Bound<'p, PyAny>> {
+    http::sync_scope(http, || match ProviderRequest::extract(&request_body)? {
+        request @ ProviderRequest::Ollama(_) => runtime::future_into_py(py, request.request(true)),
+        _ => Err(new_err::<InvalidRequestError>(
+            "Only Ollama is supported with chat mode",
+        )),
+    })
 }
 
 #[pyfunction]
-fn count_tokens<'p>(request_body: Bound<'p, PyAny>) -> PyResult<u32> {
-    if let Ok(anthropic_req) = request_body.extract::<anthropic::structs::AnthropicRequest>() {
-        // TODO! still using match instead of map,
-        // TODO! to avoid RustRover IDE error hint due to Async type missmatch
-        // TODO! but chain with map_err() is working without async (it actually have to do without it)
-        match anthropic::anthropic::get_count_tokens_anthropic(anthropic_req) {
-            Ok(tokens) => Ok(tokens),
-            Err(e) => Err(PyException::new_err(e.to_string())),
+#[pyo3(signature = (request_body, http=None))]
+fn count_tokens_async<'p>(
+    py: Python<'p>,
+    request_body: Bound<'p, PyAny>,
+    http: Option<http::HttpSettings>,
+) -> PyResult<Bound<'p, PyAny>> {
+    http::sync_scope(http, || {
+        let request = ProviderRequest::extract(&request_body)?;
+        runtime::future_into_py(py, request.count_tokens())
+    })
+}
+
+/// The markdown of every page of a PDF or image, read by the OCR model of Mistral
+#[pyfunction]
+#[pyo3(signature = (document, model=mistral::mistral::MISTRAL_OCR_MODEL, http=None))]
+fn ocr(
+    py: Python<'_>,
+    document: Bound<'_, PyAny>,
+    model: &str,
+    http: Option<http::HttpSettings>,
+) -> PyResult<Vec<String>> {
+    http::sync_scope(http, || {
+        let document = if let Ok(content) = document.extract::<message::Content>() {
+            match content.ctx {
+                message::ContentTypeInner::Document(doc) => doc,
+                _ => return Err(new_err::<InvalidRequestError>("Content is not a document")),
+            }
+        } else {
+            document.extract::<message::DocumentContent>()?
+        };
+        match py.detach(|| mistral::mistral::get_ocr_mistral(document, model)) {
+            Ok(pages) => Ok(pages),
+            Err(e) => Err(to_py_err(e)),
         }
-    } else if let Ok(_openai_req) = request_body.extract::<openai::structs::OpenAIRequest>() {
-        // openai::openai::get_count_tokens_openai(openai_req)
-        //     .map_err(|e| PyException::new_err(e.to_string()))
-        Ok(0u32) // TODO! OpenAI is having problems with counting tokens for now
-    } else if let Ok(_ollama_req) = request_body.extract::<ollama::structs::OllamaRequest>() {
-        Ok(0u32) // TODO! Ollama is not necessary to count tokens for now
-    } else {
-        Err(PyException::new_err("Invalid request body"))
-    }
+    })
 }
 
 /// A Python module implemented in Rust.
@@ -164,18 +308,69 @@ fn count_tokens<'p>(request_body: Bound<'p, PyAny>) -> PyResult<u32> {
 fn goldenai(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<anthropic::structs::AnthropicRequest>()?;
     m.add_class::<openai::structs::OpenAIRequest>()?;
+    m.add_class::<openai::structs::OpenAICompatRequest>()?;
     m.add_class::<ollama::structs::OllamaRequest>()?;
+    m.add_class::<gemini::structs::GeminiRequest>()?;
+    m.add_class::<mistral::structs::MistralRequest>()?;
 
     m.add_class::<message::Message>()?;
     m.add_class::<message::TextContent>()?;
-    m.add_class::<message::DocumentContent>()?;
     m.add_class::<message::DocumentSourceContent>()?;
+    m.add_class::<message::DocumentContent>()?;
     m.add_class::<message::Content>()?;
+    m.add_class::<message::ToolResult>()?;
+    m.add_class::<message::ThinkingContent>()?;
+
+    m.add_class::<tools::Tool>()?;
+    m.add_class::<generation::GenerationConfig>()?;
+    m.add_class::<tools::ToolCall>()?;
 
     m.add_class::<response::LLMResponse>()?;
+    m.add_class::<response::Usage>()?;
+    m.add_class::<registry::ModelInfo>()?;
+    m.add_class::<stream::StreamEvent>()?;
+    m.add_class::<stream::LLMStream>()?;
+    m.add_class::<agent::AgentStep>()?;
+    m.add_class::<agent::AgentResult>()?;
+    m.add_class::<retry::RetryPolicy>()?;
+    m.add_class::<http::HttpSettings>()?;
+    m.add_class::<client::ProviderSettings>()?;
+    m.add_class::<client::GoldenAIClient>()?;
+    m.add_class::<openai::azure::AzureSettings>()?;
+
+    error::register(m)?;
+    registry::init();
 
     m.add_function(wrap_pyfunction!(send, m)?)?;
     m.add_function(wrap_pyfunction!(count_tokens, m)?)?;
     m.add_function(wrap_pyfunction!(chat, m)?)?;
+    m.add_function(wrap_pyfunction!(send_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(send_async, m)?)?;
+    m.add_function(wrap_pyfunction!(chat_async, m)?)?;
+    m.add_function(wrap_pyfunction!(count_tokens_async, m)?)?;
+    m.add_function(wrap_pyfunction!(agent::run_agent, m)?)?;
+    m.add_function(wrap_pyfunction!(ocr, m)?)?;
+    m.add_function(wrap_pyfunction!(retry::set_retry_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(http::set_http_settings, m)?)?;
+    m.add_function(wrap_pyfunction!(openai::azure::set_azure_settings, m)?)?;
+    m.add_function(wrap_pyfunction!(registry::list_models, m)?)?;
+    m.add_function(wrap_pyfunction!(registry::model_info, m)?)?;
+    m.add_function(wrap_pyfunction!(registry::load_models, m)?)?;
     Ok(())
 }
+
+#[test]
+fn test_custom_model() {
+    let model: SupportedModels = serde_json::from_str("\"llama3.2:3b\"").unwrap();
+    assert_eq!(model, SupportedModels::Custom("llama3.2:3b".to_string()));
+    assert_eq!(serde_json::to_string(&model).unwrap(), "\"llama3.2:3b\"");
+    // a pinned snapshot is sent as is, with the capabilities and prices of its model
+    let snapshot = SupportedModels::from_str("gpt-4.1-2025-04-14").unwrap();
+    assert_eq!(
+        snapshot,
+        SupportedModels::Custom("gpt-4.1-2025-04-14".to_string())
+    );
+    assert_eq!(snapshot.to_str(), "gpt-4.1-2025-04-14");
+    assert_eq!(snapshot.info().unwrap().name, "gpt-4.1");
+    assert!(SupportedModels::from_str("").is_err());
+}
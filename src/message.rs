@@ -1,16 +1,20 @@
 use crate::SupportedModels;
+use crate::tools::ToolCall;
 use base64::Engine;
-use pyo3::exceptions::{PyException, PyTypeError};
+use pyo3::exceptions::{PyDeprecationWarning, PyException, PyTypeError, PyValueError};
 use pyo3::prelude::PyAnyMethods;
 use pyo3::types::PyType;
-use pyo3::{Bound, FromPyObject, IntoPyObject, PyAny, PyResult, pyclass, pymethods};
-use serde::{Serialize, Serializer};
+use pyo3::{Bound, FromPyObject, IntoPyObject, PyAny, PyErr, PyResult, Python, pyclass, pymethods};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Value, json};
 use std::path::PathBuf;
 
+/// The base64 source of a document in the schema of Anthropic, as documents were stored before
+/// they were encoded at send time; only `DocumentContent.source` (deprecated) hands it out
 #[derive(Serialize, Clone, Debug)]
-#[pyclass(dict, get_all, set_all, subclass)]
+#[pyclass(dict, get_all, frozen)]
 pub struct DocumentSourceContent {
-    // Anthropic schema
     #[serde(rename = "type")]
     pub(crate) content_type: String, // "base64"
     pub(crate) media_type: String, // "image/jpeg"
@@ -24,28 +28,49 @@ impl DocumentSourceContent {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+/// `llm` of DocumentContent and Content.from_document is ignored, documents are encoded for the
+/// provider of the request when they are sent
+fn warn_llm_ignored() -> PyResult<()> {
+    Python::attach(|py| {
+        PyErr::warn(
+            py,
+            &py.get_type::<PyDeprecationWarning>(),
+            c"llm is deprecated and ignored, documents are encoded for the provider of the request when they are sent",
+            1,
+        )
+    })
+}
+
+/// A file (image or PDF) as it is, encoded for the provider when the request is sent
+#[derive(Clone)]
 #[pyclass(dict, get_all, set_all, subclass)]
 pub struct DocumentContent {
-    // Anthropic schema
-    #[serde(rename = "type")]
-    pub content_type: String, // "image" (Anthropic) or "input_file" (OpenAI)
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub source: Option<DocumentSourceContent>, // Anthropic
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub file_data: Option<String>, // OpenAI
+    pub data: Vec<u8>,
+    pub media_type: String, // "image/jpeg", "application/pdf", ...
+    pub filename: Option<String>,
+}
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub filename: Option<String>, // OpenAI
+impl std::fmt::Debug for DocumentContent {
+    // the data would flood every __repr__ of a message
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DocumentContent")
+            .field("media_type", &self.media_type)
+            .field("filename", &self.filename)
+            .field("data", &format_args!("<{} bytes>", self.data.len()))
+            .finish()
+    }
 }
 
 #[pymethods]
 impl DocumentContent {
+    /// `llm` is deprecated and ignored, the document is encoded for the provider of the request
+    /// when it is sent
     #[new]
     #[pyo3(signature = (path, llm=None))]
     pub fn new(path: &str, llm: Option<SupportedModels>) -> PyResult<Self> {
+        if llm.is_some() {
+            warn_llm_ignored()?;
+        }
         let path = PathBuf::from(path);
 
         // Check if file exists
@@ -61,22 +86,22 @@ impl DocumentContent {
             .extension()
             .and_then(|s| s.to_str())
             .map(|s| s.to_lowercase())
-            .unwrap();
+            .unwrap_or_default();
 
-        // Determine content type and media type based on file extension
-        let (content_type, media_type) = match ext.as_str() {
+        // Determine media type based on file extension
+        let media_type = match ext.as_str() {
             // Image types
-            "jpg" | "image" => ("image", "image/jpeg"),
-            "png" => ("image", "image/png"),
-            "gif" => ("image", "image/gif"),
-            "bmp" => ("image", "image/bmp"),
-            "webp" => ("image", "image/webp"),
-            "svg" => ("image", "image/svg+xml"),
-            "tiff" | "tif" => ("image", "image/tiff"),
-            "heic" | "heif" => ("image", "image/heic"),
+            "jpg" | "jpeg" | "image" => "image/jpeg",
+            "png" => "image/png",
+            "gif" => "image/gif",
+            "bmp" => "image/bmp",
+            "webp" => "image/webp",
+            "svg" => "image/svg+xml",
+            "tiff" | "tif" => "image/tiff",
+            "heic" | "heif" => "image/heic",
 
             // PDF
-            "pdf" => ("document", "application/pdf"),
+            "pdf" => "application/pdf",
 
             // Unsupported type
             _ => {
@@ -87,73 +112,103 @@ impl DocumentContent {
             }
         };
 
-        let file_name = path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_string())
-            .unwrap();
-
-        // Read file and encode as base64
         let data = std::fs::read(&path)
             .map_err(|e| PyException::new_err(format!("Failed to read file: {}", e)))?;
-        let data = base64::engine::general_purpose::STANDARD.encode(&data);
-
-        match llm {
-            Some(models) => match models {
-                SupportedModels::Claude35HaikuLatest => Ok(Self {
-                    content_type: content_type.to_string(),
-                    source: Some(DocumentSourceContent {
-                        content_type: "base64".to_string(),
-                        media_type: media_type.to_string(),
-                        data: data.to_string(),
-                    }),
-                    file_data: None,
-                    filename: None,
-                }),
-                SupportedModels::GPT41Nano | SupportedModels::GPT41 => Ok(Self {
-                    content_type: {
-                        if content_type == "document" {
-                            "input_file".to_string()
-                        } else if content_type == "image" {
-                            "input_image".to_string()
-                        } else {
-                            content_type.to_string()
-                        }
-                    },
-                    source: None,
-                    file_data: Some(format!("data:{};base64,{}", media_type, data.to_string())),
-                    filename: Some(file_name),
-                }),
-
-                _ => Err(PyException::new_err(
-                    "Unsupported LLM for processing document",
-                )),
-            },
-            None => {
-                // default using OpenAI
-                Ok(Self {
-                    content_type: {
-                        if content_type == "document" {
-                            "input_file".to_string()
-                        } else if content_type == "image" {
-                            "input_image".to_string()
-                        } else {
-                            content_type.to_string()
-                        }
-                    },
-                    source: None,
-                    file_data: Some(format!("data:{};base64,{}", media_type, data.to_string())),
-                    filename: Some(file_name),
-                })
-            }
+
+        Ok(Self {
+            data,
+            media_type: media_type.to_string(),
+            filename: path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_string()),
+        })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (data, media_type, filename=None))]
+    fn from_bytes(data: Vec<u8>, media_type: &str, filename: Option<&str>) -> Self {
+        Self {
+            data,
+            media_type: media_type.to_string(),
+            filename: filename.map(|s| s.to_string()),
         }
     }
 
+    /// Deprecated, the document in the schema of Anthropic
+    #[getter]
+    fn source(&self, py: Python<'_>) -> PyResult<DocumentSourceContent> {
+        PyErr::warn(
+            py,
+            &py.get_type::<PyDeprecationWarning>(),
+            c"DocumentContent.source is deprecated, use data and media_type",
+            1,
+        )?;
+        Ok(DocumentSourceContent {
+            content_type: "base64".to_string(),
+            media_type: self.media_type.clone(),
+            data: self.base64(),
+        })
+    }
+
     fn __repr__(&self) -> PyResult<String> {
         Ok(format!("{self:?}"))
     }
 }
 
+impl DocumentContent {
+    pub(crate) fn is_image(&self) -> bool {
+        self.media_type.starts_with("image/")
+    }
+
+    pub(crate) fn base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+
+    pub(crate) fn data_uri(&self) -> String {
+        format!("data:{};base64,{}", self.media_type, self.base64())
+    }
+
+    /// `input_image` or `input_file` of the OpenAI Responses API
+    pub(crate) fn to_openai(&self) -> Value {
+        match self.is_image() {
+            true => json!({"type": "input_image", "image_url": self.data_uri()}),
+            false => json!({
+                "type": "input_file",
+                "filename": self.filename.as_deref().unwrap_or("document"),
+                "file_data": self.data_uri(),
+            }),
+        }
+    }
+
+    /// `inline_data` of Gemini
+    pub(crate) fn to_gemini(&self) -> Value {
+        json!({"inlineData": {"mimeType": self.media_type, "data": self.base64()}})
+    }
+}
+
+impl Serialize for DocumentContent {
+    // Anthropic schema, the other providers are converted explicitly
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry(
+            "type",
+            match self.is_image() {
+                true => "image",
+                false => "document",
+            },
+        )?;
+        map.serialize_entry(
+            "source",
+            &json!({"type": "base64", "media_type": self.media_type, "data": self.base64()}),
+        )?;
+        map.end()
+    }
+}
+
 #[derive(Serialize, Clone, Debug)]
 #[pyclass(dict, get_all, set_all, subclass)]
 pub struct TextContent {
@@ -178,10 +233,82 @@ impl TextContent {
     }
 }
 
+#[derive(Serialize, Clone, Debug)]
+#[pyclass(dict, get_all, set_all, subclass)]
+pub struct ToolResult {
+    // Anthropic schema
+    #[serde(rename = "type")]
+    pub(crate) content_type: String, // "tool_result"
+    #[serde(rename = "tool_use_id")]
+    pub(crate) tool_call_id: String,
+    #[serde(skip)]
+    pub(crate) name: Option<String>, // Ollama
+    pub(crate) content: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) is_error: bool,
+}
+
+#[pymethods]
+impl ToolResult {
+    #[new]
+    #[pyo3(signature = (tool_call_id, content, name=None, is_error=false))]
+    pub fn new(tool_call_id: &str, content: &str, name: Option<&str>, is_error: bool) -> Self {
+        Self {
+            content_type: "tool_result".to_string(),
+            tool_call_id: tool_call_id.to_string(),
+            name: name.map(|s| s.to_string()),
+            content: content.to_string(),
+            is_error,
+        }
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("{self:?}"))
+    }
+}
+
+/// A thinking block of Anthropic, it must be sent back unchanged (including the signature) when
+/// the conversation continues
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[pyclass(dict, get_all, subclass)]
+pub struct ThinkingContent {
+    #[serde(rename = "type")]
+    pub(crate) content_type: String, // "thinking" or "redacted_thinking"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) thinking: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) data: Option<String>, // the encrypted thinking of a redacted_thinking block
+}
+
+#[pymethods]
+impl ThinkingContent {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "ThinkingContent<type={:?},thinking={:?}>",
+            self.content_type, self.thinking
+        ))
+    }
+}
+
+impl ThinkingContent {
+    /// A thinking or redacted_thinking block of an Anthropic response
+    pub(crate) fn from_anthropic(block: &Value) -> Option<Self> {
+        match block["type"].as_str()? {
+            "thinking" | "redacted_thinking" => serde_json::from_value(block.clone()).ok(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Clone, Debug, IntoPyObject, FromPyObject)]
 pub enum ContentTypeInner {
     Document(DocumentContent),
     Text(TextContent),
+    ToolCall(ToolCall),
+    ToolResult(ToolResult),
+    Thinking(ThinkingContent), // Anthropic only, skipped for the other providers
 }
 
 impl ContentTypeInner {
@@ -189,6 +316,9 @@ impl ContentTypeInner {
         match self {
             ContentTypeInner::Document(doc) => format!("{:?}", doc.__repr__().unwrap().to_string()),
             ContentTypeInner::Text(text) => format!("{:?}", text.__repr__().unwrap().to_string()),
+            ContentTypeInner::ToolCall(call) => format!("{:?}", call.__repr__().unwrap()),
+            ContentTypeInner::ToolResult(result) => format!("{:?}", result.__repr__().unwrap()),
+            ContentTypeInner::Thinking(thinking) => format!("{:?}", thinking.__repr__().unwrap()),
         }
     }
 }
@@ -197,6 +327,26 @@ impl ContentTypeInner {
 #[pyclass(dict, get_all, set_all)]
 pub struct Content {
     pub ctx: ContentTypeInner,
+    pub cache_control: Option<String>, // Anthropic prompt caching, the ttl "5m" or "1h"
+}
+
+/// The `cache_control` of Anthropic for a prompt cached for `ttl`
+pub(crate) fn cache_control(ttl: &str) -> Value {
+    match ttl {
+        // the default of Anthropic, sent without ttl to need no beta header
+        "5m" => json!({"type": "ephemeral"}),
+        _ => json!({"type": "ephemeral", "ttl": ttl}),
+    }
+}
+
+pub(crate) fn check_cache_control(ttl: Option<&str>) -> PyResult<Option<String>> {
+    match ttl {
+        None | Some("5m") | Some("1h") => Ok(ttl.map(|ttl| ttl.to_string())),
+        Some(ttl) => Err(PyValueError::new_err(format!(
+            "cache_control must be \"5m\" or \"1h\", got {:?}",
+            ttl
+        ))),
+    }
 }
 
 impl Serialize for Content {
@@ -204,9 +354,25 @@ impl Serialize for Content {
     where
         S: Serializer,
     {
+        if let Some(ttl) = &self.cache_control {
+            // the other providers cache on their own and never see the breakpoint
+            let mut block = match &self.ctx {
+                ContentTypeInner::Document(doc) => serde_json::to_value(doc),
+                ContentTypeInner::Text(text) => serde_json::to_value(text),
+                ContentTypeInner::ToolCall(call) => serde_json::to_value(call),
+                ContentTypeInner::ToolResult(result) => serde_json::to_value(result),
+                ContentTypeInner::Thinking(thinking) => serde_json::to_value(thinking),
+            }
+            .map_err(serde::ser::Error::custom)?;
+            block["cache_control"] = cache_control(ttl);
+            return block.serialize(serializer);
+        }
         match &self.ctx {
             ContentTypeInner::Document(doc) => doc.serialize(serializer),
             ContentTypeInner::Text(text) => text.serialize(serializer),
+            ContentTypeInner::ToolCall(call) => call.serialize(serializer),
+            ContentTypeInner::ToolResult(result) => result.serialize(serializer),
+            ContentTypeInner::Thinking(thinking) => thinking.serialize(serializer),
         }
     }
 }
@@ -214,14 +380,33 @@ impl Serialize for Content {
 #[pymethods]
 impl Content {
     #[new]
-    fn new<'p>(content: Bound<'p, PyAny>) -> PyResult<Self> {
+    #[pyo3(signature = (content, cache_control=None))]
+    fn new<'p>(content: Bound<'p, PyAny>, cache_control: Option<&str>) -> PyResult<Self> {
+        let cache_control = check_cache_control(cache_control)?;
         if let Ok(text) = content.extract::<TextContent>() {
             Ok(Self {
                 ctx: ContentTypeInner::Text(text),
+                cache_control,
             })
         } else if let Ok(doc) = content.extract::<DocumentContent>() {
             Ok(Self {
                 ctx: ContentTypeInner::Document(doc),
+                cache_control,
+            })
+        } else if let Ok(result) = content.extract::<ToolResult>() {
+            Ok(Self {
+                ctx: ContentTypeInner::ToolResult(result),
+                cache_control,
+            })
+        } else if let Ok(call) = content.extract::<ToolCall>() {
+            Ok(Self {
+                ctx: ContentTypeInner::ToolCall(call),
+                cache_control,
+            })
+        } else if let Ok(thinking) = content.extract::<ThinkingContent>() {
+            Ok(Self {
+                ctx: ContentTypeInner::Thinking(thinking),
+                cache_control,
             })
         } else {
             Err(PyTypeError::new_err("Invalid content type"))
@@ -229,23 +414,55 @@ impl Content {
     }
 
     #[classmethod]
-    #[pyo3(signature = (text))]
-    fn from_text(_cls: Bound<'_, PyType>, text: &str) -> PyResult<Self> {
+    #[pyo3(signature = (text, cache_control=None))]
+    fn from_text(
+        _cls: Bound<'_, PyType>,
+        text: &str,
+        cache_control: Option<&str>,
+    ) -> PyResult<Self> {
+        let cache_control = check_cache_control(cache_control)?;
         Ok(Self {
             ctx: ContentTypeInner::Text(TextContent::new(text)?),
+            cache_control,
         })
     }
 
     #[classmethod]
-    #[pyo3(signature = (path, llm=None))]
-    fn from_document<'p>(_cls: Bound<'p, PyType>, path: &str, llm: Option<&str>) -> PyResult<Self> {
-        let _llm = Some(match llm {
-            // default document llm is GPT41Nano
-            None => SupportedModels::GPT41Nano,
-            _ => SupportedModels::from_str(llm.unwrap()).unwrap(),
-        });
+    #[pyo3(signature = (path, llm=None, cache_control=None))]
+    fn from_document<'p>(
+        _cls: Bound<'p, PyType>,
+        path: &str,
+        llm: Option<&str>,
+        cache_control: Option<&str>,
+    ) -> PyResult<Self> {
+        let cache_control = check_cache_control(cache_control)?;
+        if llm.is_some() {
+            warn_llm_ignored()?;
+        }
+        Ok(Self {
+            ctx: ContentTypeInner::Document(DocumentContent::new(path, None)?),
+            cache_control,
+        })
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (tool_call, content, is_error=false, cache_control=None))]
+    fn from_tool_result(
+        _cls: Bound<'_, PyType>,
+        tool_call: &ToolCall,
+        content: &str,
+        is_error: bool,
+        cache_control: Option<&str>,
+    ) -> PyResult<Self> {
+        let cache_control = check_cache_control(cache_control)?;
         Ok(Self {
-            ctx: ContentTypeInner::Document(DocumentContent::new(path, _llm)?),
+            ctx: ContentTypeInner::ToolResult(ToolResult::new(
+                &tool_call.id,
+                content,
+                Some(&tool_call.name),
+                is_error,
+            )),
+            cache_control,
         })
     }
 
@@ -275,3 +492,24 @@ impl Message {
         Ok(format!("{self:?}"))
     }
 }
+
+#[test]
+fn test_document_wire_formats() {
+    let doc = DocumentContent::from_bytes(b"%PDF".to_vec(), "application/pdf", Some("a.pdf"));
+    assert_eq!(
+        serde_json::to_value(&doc).unwrap(),
+        json!({"type": "document", "source": {"type": "base64", "media_type": "application/pdf", "data": "JVBERg=="}})
+    );
+    assert_eq!(
+        doc.to_openai(),
+        json!({"type": "input_file", "filename": "a.pdf", "file_data": "data:application/pdf;base64,JVBERg=="})
+    );
+    assert_eq!(doc.to_gemini()["inlineData"]["mimeType"], "application/pdf");
+
+    let image = DocumentContent::from_bytes(vec![0xff, 0xd8], "image/jpeg", None);
+    assert_eq!(serde_json::to_value(&image).unwrap()["type"], "image");
+    assert_eq!(
+        image.to_openai()["image_url"],
+        "data:image/jpeg;base64,/9g="
+    );
+}
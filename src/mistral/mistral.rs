@@ -0,0 +1,193 @@
+use anyhow::Result;
+use reqwest::Response;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::MISTRAL_API_URL;
+use crate::error::{ErrorKind, LocalError};
+use crate::message::{ContentTypeInner, DocumentContent, TextContent};
+use crate::mistral::structs::{MistralRequest, OCRRequest, OCRResponse};
+use crate::openai::completions::ChatCompletionsResponse;
+use crate::response::LLMResponse;
+use crate::retry::send_with_retry;
+use crate::runtime::{client, runtime};
+
+pub const MISTRAL_OCR_MODEL: &str = "mistral-ocr-latest";
+const OCR_CACHE_SIZE: usize = 32;
+
+// the pages of the last documents read by OCR, by `ocr_key`: a conversation sends its documents
+// again with every turn, and counting its tokens sends them once more
+static OCR_PAGES: Mutex<VecDeque<(u64, Vec<String>)>> = Mutex::new(VecDeque::new());
+
+pub fn get_response_mistral(request_body: MistralRequest) -> Result<LLMResponse> {
+    runtime().block_on(request_mistral(request_body))
+}
+
+pub fn get_count_tokens_mistral(request_body: MistralRequest) -> Result<u32> {
+    count_tokens_mistral(request_body)
+}
+
+pub fn get_ocr_mistral(document: DocumentContent, model: &str) -> Result<Vec<String>> {
+    runtime().block_on(ocr_mistral(&document, model))
+}
+
+pub(crate) async fn request_mistral(request_body: MistralRequest) -> Result<LLMResponse> {
+    let request_body = with_ocr_documents(request_body).await?;
+    let response = post_mistral(
+        "chat/completions",
+        &request_body.to_chat_completions(false)?,
+    )
+    .await?;
+    let response: ChatCompletionsResponse = response.json().await?;
+    Ok(response.to_llm_response(request_body.model))
+}
+
+/// Send the request with `stream` enabled and hand back the raw SSE response
+pub(crate) async fn request_mistral_stream(request_body: MistralRequest) -> Result<Response> {
+    let request_body = with_ocr_documents(request_body).await?;
+    post_mistral("chat/completions", &request_body.to_chat_completions(true)?).await
+}
+
+/// Mistral has no endpoint to count tokens and its tokenizer is not available locally, so
+/// counting is not supported (rather than paid for with a completion)
+pub(crate) fn count_tokens_mistral(request_body: MistralRequest) -> Result<u32> {
+    Err(LocalError::new(
+        ErrorKind::InvalidRequest,
+        format!(
+            "Counting tokens is not supported for Mistral ({}), its tokenizer is unknown",
+            request_body.model.to_str()
+        ),
+    )
+    .into())
+}
+
+/// The markdown of every page of a PDF (or image) document
+pub(crate) async fn ocr_mistral(document: &DocumentContent, model: &str) -> Result<Vec<String>> {
+    let response = post_mistral("ocr", &OCRRequest::new(model, document)).await?;
+    let response: OCRResponse = response.json().await?;
+    Ok(response
+        .pages
+        .into_iter()
+        .map(|page| page.markdown)
+        .collect())
+}
+
+fn ocr_key(document: &DocumentContent, model: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (model, &document.media_type, &document.data).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The markdown of the pages of `document`, read by OCR only if it is not in the cache
+async fn cached_ocr_mistral(document: &DocumentContent, model: &str) -> Result<Vec<String>> {
+    let key = ocr_key(document, model);
+    let cached = OCR_PAGES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(cached_key, _)| *cached_key == key)
+        .map(|(_, pages)| pages.clone());
+    if let Some(pages) = cached {
+        return Ok(pages);
+    }
+
+    let pages = ocr_mistral(document, model).await?;
+    let mut cache = OCR_PAGES.lock().unwrap();
+    if cache.len() >= OCR_CACHE_SIZE {
+        cache.pop_front();
+    }
+    cache.push_back((key, pages.clone()));
+    Ok(pages)
+}
+
+/// Chat completions do not accept PDFs, they are replaced by the markdown of their pages
+async fn with_ocr_documents(mut request_body: MistralRequest) -> Result<MistralRequest> {
+    for message in request_body.messages.iter_mut() {
+        for content in message.content.iter_mut() {
+            let ContentTypeInner::Document(document) = &content.ctx else {
+                continue;
+            };
+            if document.media_type != "application/pdf" {
+                continue;
+            }
+            let pages = cached_ocr_mistral(document, MISTRAL_OCR_MODEL).await?;
+            content.ctx = ContentTypeInner::Text(TextContent {
+                content_type: "text".to_string(),
+                text: pages.join("\n\n"),
+            });
+        }
+    }
+    Ok(request_body)
+}
+
+async fn post_mistral<T: Serialize>(path: &str, body: &T) -> Result<Response> {
+    let api_key = crate::client::api_key("mistral", "MISTRAL_API_KEY")?;
+
+    let url = format!(
+        "{}/{}",
+        crate::client::base_url("mistral", MISTRAL_API_URL),
+        path
+    );
+    let request = client("mistral", &url)?
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("content-type", "application/json")
+        .json(body);
+
+    send_with_retry("mistral", request).await
+}
+
+#[test]
+fn test_with_ocr_documents() {
+    use crate::SupportedModels;
+    use crate::message::{Content, Message};
+
+    let document = DocumentContent {
+        data: b"%PDF-1.7 invoice".to_vec(),
+        media_type: "application/pdf".to_string(),
+        filename: Some("invoice.pdf".to_string()),
+    };
+    // a document in the cache is not sent to /ocr again
+    OCR_PAGES.lock().unwrap().push_back((
+        ocr_key(&document, MISTRAL_OCR_MODEL),
+        vec!["# Invoice".to_string(), "Total: 42 EUR".to_string()],
+    ));
+    let request = MistralRequest {
+        model: SupportedModels::MistralSmallLatest,
+        system: None,
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: vec![
+                Content {
+                    ctx: ContentTypeInner::Document(document),
+                    cache_control: None,
+                },
+                Content {
+                    ctx: ContentTypeInner::Text(TextContent {
+                        content_type: "text".to_string(),
+                        text: "What is the total?".to_string(),
+                    }),
+                    cache_control: None,
+                },
+            ],
+        }],
+        max_tokens: None,
+        tools: vec![],
+        tool_choice: None,
+        format: None,
+        generation_config: None,
+    };
+
+    let request = runtime().block_on(with_ocr_documents(request)).unwrap();
+    let body = serde_json::to_value(request.to_chat_completions(false).unwrap()).unwrap();
+    assert_eq!(
+        body["messages"][0]["content"][0],
+        serde_json::json!({"type": "text", "text": "# Invoice\n\nTotal: 42 EUR"})
+    );
+    assert_eq!(
+        body["messages"][0]["content"][1]["text"],
+        "What is the total?"
+    );
+}
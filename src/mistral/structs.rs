@@ -0,0 +1,218 @@
+use anyhow::Result;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::SupportedModels;
+use crate::generation::GenerationConfig;
+use crate::message::{Content, ContentTypeInner, DocumentContent, Message, TextContent};
+use crate::openai::completions::ChatCompletionsRequest;
+use crate::response::LLMResponse;
+use crate::schema::Json;
+use crate::tools::Tool;
+
+#[derive(Serialize, Clone, Debug)]
+#[pyclass(dict, get_all, set_all, subclass)]
+pub struct MistralRequest {
+    pub(crate) model: SupportedModels,
+    pub(crate) system: Option<String>,
+    pub(crate) messages: Vec<Message>,
+    pub(crate) max_tokens: Option<u32>,
+    pub(crate) tools: Vec<Tool>,
+    pub(crate) tool_choice: Option<String>,
+    pub(crate) format: Option<Json>, // structured output
+    pub(crate) generation_config: Option<GenerationConfig>,
+}
+
+#[pymethods]
+impl MistralRequest {
+    #[new]
+    #[pyo3(signature = (model, messages, prompt=None, max_tokens=None, tools=None, tool_choice=None, generation_config=None))]
+    pub fn new(
+        model: &str,
+        messages: Vec<Message>,
+        prompt: Option<&str>,
+        max_tokens: Option<u32>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<&str>,
+        generation_config: Option<GenerationConfig>,
+    ) -> PyResult<Self> {
+        if let Some(config) = &generation_config {
+            config
+                .to_mistral()
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        }
+        Ok(Self {
+            model: SupportedModels::from_str(model)
+                .map_err(|e| PyValueError::new_err(e.to_string()))?,
+            system: prompt.map(|s| s.to_string()),
+            messages,
+            max_tokens,
+            tools: tools.unwrap_or_default(),
+            tool_choice: tool_choice.map(|s| s.to_string()),
+            format: None,
+            generation_config,
+        })
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("{self:?}"))
+    }
+
+    pub fn add_response(&mut self, response: LLMResponse) {
+        let mut content: Vec<Content> = response
+            .content
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|block| !block.text.is_empty())
+            .map(|block| Content {
+                ctx: ContentTypeInner::Text(TextContent {
+                    content_type: "text".to_string(),
+                    text: block.text,
+                }),
+                cache_control: None,
+            })
+            .collect();
+        content.extend(response.tool_calls.into_iter().map(|call| Content {
+            ctx: ContentTypeInner::ToolCall(call),
+            cache_control: None,
+        }));
+
+        self.messages.push(Message {
+            role: "assistant".to_string(),
+            content,
+        });
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+}
+
+impl MistralRequest {
+    pub fn to_chat_completions(&self, stream: bool) -> Result<ChatCompletionsRequest> {
+        let mut body = ChatCompletionsRequest::new(
+            self.model.to_str(),
+            self.system.as_deref(),
+            &self.messages,
+            self.max_tokens,
+            &self.tools,
+            self.tool_choice.as_deref(),
+        );
+        body.response_format = self.format.as_ref().map(|format| format.0.clone());
+        body.stream = stream;
+        if let Some(config) = &self.generation_config {
+            body.params = config.to_mistral()?;
+        }
+        Ok(body)
+    }
+}
+
+/// Body of `/ocr`
+#[derive(Serialize)]
+pub struct OCRRequest {
+    pub model: String,
+    pub document: serde_json::Value,
+}
+
+impl OCRRequest {
+    pub fn new(model: &str, document: &DocumentContent) -> Self {
+        let url = document.data_uri();
+        Self {
+            model: model.to_string(),
+            document: match document.is_image() {
+                true => json!({"type": "image_url", "image_url": url}),
+                false => json!({"type": "document_url", "document_url": url}),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OCRPage {
+    pub markdown: String,
+}
+
+#[derive(Deserialize)]
+pub struct OCRResponse {
+    pub pages: Vec<OCRPage>,
+}
+
+#[test]
+fn test_mistral_request() {
+    use crate::openai::completions::ChatCompletionsResponse;
+    use crate::tools::ToolCall;
+
+    let request = MistralRequest {
+        model: SupportedModels::MistralSmallLatest,
+        system: Some("Be brief".to_string()),
+        messages: vec![
+            Message {
+                role: "user".to_string(),
+                content: vec![Content {
+                    ctx: ContentTypeInner::Text(TextContent {
+                        content_type: "text".to_string(),
+                        text: "Weather in Berlin?".to_string(),
+                    }),
+                    cache_control: None,
+                }],
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: vec![Content {
+                    ctx: ContentTypeInner::ToolCall(ToolCall {
+                        id: "call_0".to_string(),
+                        name: "get_weather".to_string(),
+                        arguments: json!({"city": "Berlin"}),
+                    }),
+                    cache_control: None,
+                }],
+            },
+        ],
+        max_tokens: Some(100),
+        tools: vec![Tool {
+            name: "get_weather".to_string(),
+            description: None,
+            parameters: json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+        }],
+        tool_choice: Some("any".to_string()),
+        format: Some(Json(json!({"type": "json_object"}))),
+        generation_config: Some(GenerationConfig {
+            temperature: Some(0.5),
+            seed: Some(7),
+            ..Default::default()
+        }),
+    };
+
+    let body = serde_json::to_value(request.to_chat_completions(true).unwrap()).unwrap();
+    assert_eq!(body["model"], "mistral-small-latest");
+    assert_eq!(
+        body["messages"][0],
+        json!({"role": "system", "content": "Be brief"})
+    );
+    assert_eq!(body["messages"][2]["tool_calls"][0]["id"], "call_0");
+    assert_eq!(body["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(body["tool_choice"], "required");
+    assert_eq!(body["response_format"]["type"], "json_object");
+    assert_eq!(body["max_tokens"], 100);
+    assert_eq!(body["temperature"], 0.5);
+    assert_eq!(body["random_seed"], 7);
+    assert_eq!(body["stream"], true);
+
+    let response: ChatCompletionsResponse = serde_json::from_value(json!({
+        "id": "cmpl-2",
+        "model": "mistral-small-2506",
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": "Sunny, 21 degrees", "tool_calls": null},
+            "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 30, "completion_tokens": 6, "total_tokens": 36},
+    }))
+    .unwrap();
+    let response = response.to_llm_response(request.model);
+    assert_eq!(response.to_string(), "Sunny, 21 degrees");
+    assert_eq!(response.model, SupportedModels::MistralSmallLatest);
+    assert_eq!(response.stop_reason.as_deref(), Some("stop"));
+    assert_eq!(response.usage.input_tokens, 30);
+    assert_eq!(response.usage.output_tokens, 6);
+    assert!(response.tool_calls.is_empty());
+}
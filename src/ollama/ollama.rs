@@ -1,80 +1,74 @@
 use anyhow::{Result, anyhow};
+use reqwest::Response;
 use std::time::Duration;
 
 use crate::ollama::structs::{
     ConvertedOllamaRequest, OllamaChatRequest, OllamaChatResponse, OllamaRequest, OllamaResponse,
 };
 use crate::response::LLMResponse;
+use crate::retry::send_with_retry;
+use crate::runtime::{client, runtime};
 
-#[tokio::main]
-pub async fn get_response_ollama(request_body: OllamaRequest, chat: bool) -> Result<LLMResponse> {
-    request_ollama(request_body, chat).await
+pub fn get_response_ollama(request_body: OllamaRequest, chat: bool) -> Result<LLMResponse> {
+    runtime().block_on(request_ollama(request_body, chat))
 }
 
 pub async fn request_ollama(request_body: OllamaRequest, chat: bool) -> Result<LLMResponse> {
-    // check if url is connectable
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&format!("{}/api/version", request_body.url))
-        .timeout(Duration::from_secs(3))
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let err_status = response.status();
-        let error_text = response.text().await?;
-        return Err(anyhow!(
-            "Error: HTTP {}, Response: {}",
-            err_status,
-            error_text
-        ));
+    let response = post_ollama(request_body, chat, false).await?;
+
+    if chat {
+        // let response_text = response.text().await?;
+        // println!("Raw response: {}", response_text);
+        // let response: OllamaChatResponse = serde_json::from_str(&response_text)?;
+        let response: OllamaChatResponse = response.json().await?;
+        Ok(response.to_llm_response())
+    } else {
+        // let response_text = response.text().await?;
+        // println!("Raw response: {}", response_text);
+        // let response: OllamaResponse = serde_json::from_str(&response_text)?;
+        let response: OllamaResponse = response.json().await?;
+        Ok(response.to_llm_response())
     }
+}
+
+/// Send the request in stream mode and hand back the raw NDJSON response
+pub(crate) async fn request_ollama_stream(
+    request_body: OllamaRequest,
+    chat: bool,
+) -> Result<Response> {
+    post_ollama(request_body, chat, true).await
+}
+
+async fn post_ollama(request_body: OllamaRequest, chat: bool, stream: bool) -> Result<Response> {
+    if !chat && !request_body.tools.is_empty() {
+        return Err(anyhow!("Tools are only supported in chat mode with Ollama"));
+    }
+
+    // check if url is connectable
+    let client = client("ollama", &request_body.url)?;
+    let request = client
+        .get(format!("{}/api/version", request_body.url))
+        .timeout(Duration::from_secs(3));
 
-    let client = reqwest::Client::new();
+    send_with_retry("ollama", request).await?;
 
-    let response = if chat {
+    let request = if chat {
         client
             .post(format!("{}/api/{}", request_body.url, "chat"))
             .json(&OllamaChatRequest::from_ollama_request(
                 request_body,
-                false, // TODO! stream mode
-            ))
-            .send()
-            .await?
+                stream,
+            )?)
     } else {
         client
             .post(format!("{}/api/{}", request_body.url, "generate"))
             .json(&ConvertedOllamaRequest::from_ollama_request(
                 request_body,
-                false, // TODO! stream mode
-            ))
-            .send()
-            .await?
+                stream,
+            )?)
     };
 
-    if response.status().is_success() {
-        if chat {
-            // let response_text = response.text().await?;
-            // println!("Raw response: {}", response_text);
-            // let response: OllamaChatResponse = serde_json::from_str(&response_text)?;
-            let response: OllamaChatResponse = response.json().await?;
-            Ok(response.to_llm_response())
-        } else {
-            // let response_text = response.text().await?;
-            // println!("Raw response: {}", response_text);
-            // let response: OllamaResponse = serde_json::from_str(&response_text)?;
-            let response: OllamaResponse = response.json().await?;
-            Ok(response.to_llm_response())
-        }
-    } else {
-        let err_status = response.status();
-        let error_text = response.text().await?;
-        Err(anyhow!(
-            "Error: HTTP {}, Response: {}",
-            err_status,
-            error_text
-        ))
-    }
+    send_with_retry("ollama", request).await
 }
 
 #[tokio::test]
@@ -98,10 +92,14 @@ async fn test_ollama_request() {
                     content_type: "text".to_string(),
                     text: "What's your name?".to_string(),
                 }),
+                cache_control: None,
             }],
         }],
         Some("Please answer in Chinese"),
         None,
+        None,
+        None,
+        None,
     )
     .unwrap();
 
@@ -136,10 +134,14 @@ async fn test_ollama_chat() {
                     content_type: "text".to_string(),
                     text: "Why sky is blue?".to_string(),
                 }),
+                cache_control: None,
             }],
         }],
         None,
         None,
+        None,
+        None,
+        None,
     )
     .unwrap();
 
@@ -160,6 +162,7 @@ async fn test_ollama_chat() {
                 content_type: "text".to_string(),
                 text: "Please answer the question in Ukrainian".to_string(),
             }),
+            cache_control: None,
         }],
     });
 
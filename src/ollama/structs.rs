@@ -1,14 +1,20 @@
+use anyhow::{Result, anyhow};
 use base64::Engine;
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
 use pyo3::pyclass;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::path::PathBuf;
 
 use crate::SupportedModels;
 use crate::anthropic::structs::ResponseAnthropic;
-use crate::message::{Content, ContentTypeInner, Message, TextContent};
+use crate::error::{ErrorKind, LocalError};
+use crate::generation::GenerationConfig;
+use crate::message::{Content, ContentTypeInner, DocumentContent, Message, TextContent};
 use crate::response::{LLMResponse, Usage};
+use crate::schema::Json;
+use crate::tools::{Tool, ToolCall};
 
 #[derive(Serialize, Clone, Debug)]
 #[pyclass(dict, get_all, set_all, subclass)]
@@ -18,18 +24,27 @@ pub struct OllamaRequest {
     pub(crate) system: Option<String>,
     pub(crate) messages: Vec<Message>,
     pub(crate) image: Option<String>, // base64 encoded images
+    pub(crate) tools: Vec<Tool>,      // chat mode only
+    pub(crate) tool_choice: Option<String>,
+    pub(crate) format: Option<Json>,      // structured output
+    pub(crate) context: Option<Vec<i64>>, // generate mode, the tokens of the conversation so far
+    pub(crate) generation_config: Option<GenerationConfig>, // sent as `options`
 }
 
 #[pymethods]
 impl OllamaRequest {
     #[new]
-    #[pyo3(signature = (url, model, messages, prompt=None, image=None))]
+    #[pyo3(signature = (url, model, messages, prompt=None, image=None, tools=None, tool_choice=None, generation_config=None))]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         url: &str,
         model: &str,
         messages: Vec<Message>,
         prompt: Option<&str>,
         image: Option<&str>, // image path
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<&str>,
+        generation_config: Option<GenerationConfig>,
     ) -> PyResult<Self> {
         let data = match image {
             Some(image) => {
@@ -46,10 +61,16 @@ impl OllamaRequest {
 
         Ok(Self {
             url: url.to_string(),
-            model: SupportedModels::from_str(model).unwrap(),
+            model: SupportedModels::from_str(model)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?,
             system: prompt.map(|s| s.to_string()),
             messages,
             image: data,
+            tools: tools.unwrap_or_default(),
+            tool_choice: tool_choice.map(|s| s.to_string()),
+            format: None,
+            context: None,
+            generation_config,
         })
     }
 
@@ -58,15 +79,29 @@ impl OllamaRequest {
     }
 
     pub fn add_response(&mut self, response: LLMResponse) {
-        self.messages.push(Message {
-            role: "assistant".to_string(),
-            content: vec![Content {
+        let mut content: Vec<Content> = response
+            .content
+            .unwrap_or_default()
+            .into_iter()
+            .map(|block| Content {
                 ctx: ContentTypeInner::Text(TextContent {
                     content_type: "text".to_string(),
-                    text: response.content.unwrap()[0].text.clone(),
+                    text: block.text,
                 }),
-            }],
+                cache_control: None,
+            })
+            .collect();
+        content.extend(response.tool_calls.into_iter().map(|call| Content {
+            ctx: ContentTypeInner::ToolCall(call),
+            cache_control: None,
+        }));
+
+        self.messages.push(Message {
+            role: "assistant".to_string(),
+            content,
         });
+        // a response of chat mode has no context, the next generate request renders the history
+        self.context = response.context;
     }
 
     pub fn add_message(&mut self, message: Message) {
@@ -81,51 +116,152 @@ pub struct ConvertedOllamaRequest {
     pub prompt: String,
     pub stream: bool,
     pub images: Option<Vec<String>>, // base64 encoded images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Json>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<Vec<i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Map<String, Value>>,
 }
 
 impl ConvertedOllamaRequest {
-    pub fn from_ollama_request(request_body: OllamaRequest, stream: bool) -> Self {
-        Self {
+    /// With the `context` of the last response only the messages after it are sent, otherwise
+    /// the whole history is rendered into the prompt
+    pub fn from_ollama_request(request_body: OllamaRequest, stream: bool) -> Result<Self> {
+        let first = match &request_body.context {
+            Some(_) => request_body
+                .messages
+                .iter()
+                .rposition(|message| message.role == "assistant")
+                .map_or(0, |index| index + 1),
+            None => 0,
+        };
+        let messages = &request_body.messages[first..];
+        if messages.is_empty() {
+            return Err(anyhow!("The request has no message"));
+        }
+
+        // the documents of the messages are sent along with the image of `image`, which (like
+        // the system prompt) is part of the context after the first turn
+        let mut images: Vec<String> = match first {
+            0 => request_body.image.clone().into_iter().collect(),
+            _ => Vec::new(),
+        };
+        let mut turns: Vec<String> = Vec::new();
+        for message in messages {
+            let mut texts: Vec<String> = Vec::new();
+            for each_content in &message.content {
+                match &each_content.ctx {
+                    ContentTypeInner::Text(text) => texts.push(text.text.clone()),
+                    ContentTypeInner::Document(doc) => add_document(doc, &mut texts, &mut images)?,
+                    ContentTypeInner::ToolCall(_) | ContentTypeInner::ToolResult(_) => {
+                        return Err(anyhow!("Tools are only supported in chat mode with Ollama"));
+                    }
+                    ContentTypeInner::Thinking(_) => {}
+                }
+            }
+            turns.push(match messages.len() {
+                1 => texts.join("\n\n"),
+                _ => format!("{}: {}", role_name(&message.role), texts.join("\n\n")),
+            });
+        }
+        let mut prompt = turns.join("\n\n");
+        if messages.len() > 1 {
+            prompt.push_str("\n\nAssistant:");
+        }
+
+        Ok(Self {
             url: request_body.url,
             model: request_body.model.to_str().to_string(),
-            prompt: match &request_body.system {
-                Some(system) => {
-                    format!(
-                        "{}\n\nThe following is my message:\n\n{}",
-                        system,
-                        request_body.messages[0]
-                            .content
-                            .iter()
-                            .map(|each_content| match &each_content.ctx {
-                                ContentTypeInner::Text(text) => text.text.clone(),
-                                _ => panic!("Invalid content type"),
-                            })
-                            .collect::<Vec<String>>()
-                            .join("\n\n")
-                    )
-                }
-                None => {
-                    format!(
-                        "{}",
-                        request_body.messages[0]
-                            .content
-                            .iter()
-                            .map(|each_content| match &each_content.ctx {
-                                ContentTypeInner::Text(text) => text.text.clone(),
-                                _ => panic!("Invalid content type"),
-                            })
-                            .collect::<Vec<String>>()
-                            .join("\n\n")
-                    )
+            prompt: match (&request_body.system, first) {
+                (Some(system), 0) => {
+                    format!("{}\n\nThe following is my message:\n\n{}", system, prompt)
                 }
+                _ => prompt,
             },
             stream,
-            images: match request_body.image {
-                Some(image) => Some(vec![image]),
-                None => None,
-            },
+            images: (!images.is_empty()).then_some(images),
+            format: request_body.format,
+            context: request_body.context,
+            options: request_body
+                .generation_config
+                .as_ref()
+                .map(GenerationConfig::to_ollama)
+                .transpose()?,
+        })
+    }
+}
+
+fn role_name(role: &str) -> &str {
+    match role {
+        "user" => "User",
+        "assistant" => "Assistant",
+        role => role,
+    }
+}
+
+/// Ollama only takes images, a PDF is sent as the text of its pages and the pictures embedded in
+/// them (e.g. scanned pages) as images
+fn add_document(
+    doc: &DocumentContent,
+    texts: &mut Vec<String>,
+    images: &mut Vec<String>,
+) -> Result<()> {
+    if doc.is_image() {
+        images.push(doc.base64());
+        return Ok(());
+    }
+    if doc.media_type != "application/pdf" {
+        return Err(anyhow!(
+            "Unsupported document type for Ollama: {}",
+            doc.media_type
+        ));
+    }
+
+    let name = doc.filename.as_deref().unwrap_or("document");
+    // the text extraction panics on some malformed PDFs
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&doc.data))
+        .map_err(|_| anyhow!("Failed to read the PDF {}", name))?
+        .map_err(|e| anyhow!("Failed to read the PDF {}: {}", name, e))?;
+    let pages: Vec<String> = pages
+        .iter()
+        .enumerate()
+        .filter(|(_, page)| !page.trim().is_empty())
+        .map(|(index, page)| format!("Page {}:\n{}", index + 1, page.trim()))
+        .collect();
+
+    // JPEG (DCTDecode) streams are complete image files, other encodings are skipped
+    let jpegs = std::panic::catch_unwind(|| -> Result<Vec<String>> {
+        let pdf = pdf_extract::Document::load_mem(&doc.data)?;
+        let mut jpegs = Vec::new();
+        for page_id in pdf.get_pages().into_values() {
+            for image in pdf.get_page_images(page_id).unwrap_or_default() {
+                if image.filters.as_deref() == Some(&["DCTDecode".to_string()]) {
+                    jpegs.push(base64::engine::general_purpose::STANDARD.encode(image.content));
+                }
+            }
         }
+        Ok(jpegs)
+    })
+    .map_err(|_| anyhow!("Failed to read the PDF {}", name))?
+    .map_err(|e| anyhow!("Failed to read the PDF {}: {}", name, e))?;
+
+    // e.g. a scan in another encoding, which would be left out without notice
+    if pages.is_empty() && jpegs.is_empty() {
+        return Err(LocalError::new(
+            ErrorKind::InvalidRequest,
+            format!(
+                "The PDF {} has neither text nor JPEG images that could be sent to Ollama",
+                name
+            ),
+        )
+        .into());
     }
+    if !pages.is_empty() {
+        texts.push(format!("{}:\n\n{}", name, pages.join("\n\n")));
+    }
+    images.extend(jpegs);
+    Ok(())
 }
 
 #[derive(Deserialize)]
@@ -133,8 +269,43 @@ pub struct OllamaResponse {
     pub model: SupportedModels,
     pub response: String,
     pub done_reason: String,
+    #[serde(flatten)]
+    pub metrics: OllamaMetrics,
+    pub context: Option<Vec<i64>>,
+}
+
+/// The counts and durations of the final response (or chunk) of generate and chat mode
+#[derive(Deserialize, Default)]
+pub struct OllamaMetrics {
+    #[serde(default)]
+    pub prompt_eval_count: u32, // left out when the prompt is cached
+    #[serde(default)]
     pub eval_count: u32,
-    pub prompt_eval_count: u32,
+    // in nanoseconds
+    pub total_duration: Option<u64>,
+    pub load_duration: Option<u64>,
+    pub prompt_eval_duration: Option<u64>,
+    pub eval_duration: Option<u64>,
+}
+
+impl OllamaMetrics {
+    pub(crate) fn to_usage(&self) -> Usage {
+        let durations = [
+            ("total", self.total_duration),
+            ("load", self.load_duration),
+            ("prompt_eval", self.prompt_eval_duration),
+            ("eval", self.eval_duration),
+        ]
+        .into_iter()
+        .filter_map(|(name, duration)| Some((name.to_string(), duration? as f64 / 1e9)))
+        .collect();
+        Usage {
+            input_tokens: self.prompt_eval_count,
+            output_tokens: self.eval_count,
+            durations,
+            ..Default::default()
+        }
+    }
 }
 
 impl OllamaResponse {
@@ -143,16 +314,14 @@ impl OllamaResponse {
             id: "".to_string(),
             model: self.model,
             response_type: "text".to_string(),
-            usage: Usage {
-                input_tokens: self.eval_count,
-                output_tokens: self.prompt_eval_count,
-            },
+            usage: self.metrics.to_usage(),
             role: Some("ollama".to_string()), // to make sure the ResponseContent is used in fmt,
             content: Some(vec![ResponseAnthropic {
                 content_type: "text".to_string(),
                 text: self.response,
             }]),
             stop_reason: Some(self.done_reason),
+            context: self.context,
             ..Default::default()
         }
     }
@@ -163,6 +332,30 @@ pub struct OllamaChatMessage {
     pub role: String,
     pub content: String,
     pub images: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>, // role "tool" only
+}
+
+impl OllamaChatMessage {
+    pub fn to_tool_calls(&self) -> Vec<ToolCall> {
+        // Ollama does not always assign ids to tool calls
+        self.tool_calls
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter_map(|(index, call)| {
+                Some(ToolCall {
+                    id: call["id"]
+                        .as_str()
+                        .map_or(format!("call_{}", index), |id| id.to_string()),
+                    name: call["function"]["name"].as_str()?.to_string(),
+                    arguments: call["function"]["arguments"].clone(),
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Serialize)]
@@ -171,35 +364,77 @@ pub struct OllamaChatRequest {
     pub model: String,
     pub messages: Vec<OllamaChatMessage>,
     pub stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Json>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Map<String, Value>>,
 }
 
 impl OllamaChatRequest {
-    pub fn from_ollama_request(request_body: OllamaRequest, stream: bool) -> Self {
-        let ollama_messages: Vec<OllamaChatMessage> = request_body
-            .clone()
-            .messages
-            .iter()
-            .map(|message| OllamaChatMessage {
+    pub fn from_ollama_request(request_body: OllamaRequest, stream: bool) -> Result<Self> {
+        let mut ollama_messages: Vec<OllamaChatMessage> = Vec::new();
+        if let Some(system) = &request_body.system {
+            ollama_messages.push(OllamaChatMessage {
+                role: "system".to_string(),
+                content: system.clone(),
+                images: None,
+                tool_calls: None,
+                tool_name: None,
+            });
+        }
+        for message in &request_body.messages {
+            let mut texts: Vec<String> = Vec::new();
+            let mut images: Vec<String> = Vec::new();
+            let mut tool_calls: Vec<Value> = Vec::new();
+            for each_content in &message.content {
+                match &each_content.ctx {
+                    ContentTypeInner::Text(text) => texts.push(text.text.clone()),
+                    ContentTypeInner::Document(doc) => add_document(doc, &mut texts, &mut images)?,
+                    ContentTypeInner::ToolCall(call) => tool_calls.push(call.to_ollama()),
+                    // every tool result is a message of its own
+                    ContentTypeInner::ToolResult(result) => {
+                        ollama_messages.push(OllamaChatMessage {
+                            role: "tool".to_string(),
+                            content: result.content.clone(),
+                            images: None,
+                            tool_calls: None,
+                            tool_name: result.name.clone(),
+                        })
+                    }
+                    ContentTypeInner::Thinking(_) => {}
+                }
+            }
+            if texts.is_empty() && images.is_empty() && tool_calls.is_empty() {
+                continue;
+            }
+            ollama_messages.push(OllamaChatMessage {
                 role: message.role.to_string(),
-                content: message
-                    .content
-                    .iter()
-                    .map(|each_content| match &each_content.ctx {
-                        ContentTypeInner::Text(text) => text.text.clone(),
-                        _ => panic!("Invalid content type"),
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n\n"),
-                images: None, // TODO! support images
-            })
-            .collect();
+                content: texts.join("\n\n"),
+                images: (!images.is_empty()).then_some(images),
+                tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                tool_name: None,
+            });
+        }
 
-        Self {
+        Ok(Self {
             url: request_body.url,
             model: request_body.model.to_str().to_string(),
             messages: ollama_messages,
             stream,
-        }
+            // Ollama has no tool_choice, "none" is the only one that can be honoured
+            tools: match request_body.tool_choice.as_deref() {
+                Some("none") => vec![],
+                _ => request_body.tools.iter().map(Tool::to_ollama).collect(),
+            },
+            format: request_body.format,
+            options: request_body
+                .generation_config
+                .as_ref()
+                .map(GenerationConfig::to_ollama)
+                .transpose()?,
+        })
     }
 }
 
@@ -210,6 +445,8 @@ pub struct OllamaChatResponse {
     pub message: OllamaChatMessage,
     pub done: bool,
     pub done_reason: String,
+    #[serde(flatten)]
+    pub metrics: OllamaMetrics,
 }
 
 impl OllamaChatResponse {
@@ -218,10 +455,8 @@ impl OllamaChatResponse {
             id: "".to_string(),
             model: self.model,
             response_type: "text".to_string(),
-            usage: Usage {
-                input_tokens: 0,
-                output_tokens: 0,
-            },
+            usage: self.metrics.to_usage(),
+            tool_calls: self.message.to_tool_calls(),
             role: Some(self.message.role),
             content: Some(vec![ResponseAnthropic {
                 content_type: "text".to_string(),
@@ -232,3 +467,143 @@ impl OllamaChatResponse {
         }
     }
 }
+
+#[test]
+fn test_documents_ollama() {
+    let message = Message {
+        role: "user".to_string(),
+        content: vec![
+            Content {
+                ctx: ContentTypeInner::Text(TextContent::new("What does it say?").unwrap()),
+                cache_control: None,
+            },
+            Content {
+                ctx: ContentTypeInner::Document(
+                    DocumentContent::new("examples/python/test.pdf", None).unwrap(),
+                ),
+                cache_control: None,
+            },
+            Content {
+                ctx: ContentTypeInner::Document(
+                    DocumentContent::new("examples/python/white.jpg", None).unwrap(),
+                ),
+                cache_control: None,
+            },
+        ],
+    };
+    let request = OllamaRequest::new(
+        "http://localhost:11434",
+        "qwen2.5vl",
+        vec![message],
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+
+    let chat = OllamaChatRequest::from_ollama_request(request.clone(), false).unwrap();
+    assert!(chat.messages[0].content.contains("test.pdf:\n\nPage 1:"));
+    assert_eq!(chat.messages[0].images.as_ref().unwrap().len(), 1);
+
+    let generate = ConvertedOllamaRequest::from_ollama_request(request, false).unwrap();
+    assert!(generate.prompt.starts_with("What does it say?"));
+    assert_eq!(generate.images.unwrap().len(), 1);
+}
+
+#[test]
+fn test_blank_pdf_ollama() {
+    use pdf_extract::{Dictionary, Document, Object, Stream};
+
+    // a PDF with a single empty page, nothing of it can be sent
+    let mut pdf = Document::with_version("1.5");
+    let pages_id = pdf.new_object_id();
+    let content_id = pdf.add_object(Stream::new(Dictionary::new(), Vec::new()));
+    let mut page = Dictionary::new();
+    page.set("Type", Object::Name(b"Page".to_vec()));
+    page.set("Parent", pages_id);
+    page.set("Contents", content_id);
+    page.set("Resources", Dictionary::new());
+    page.set("MediaBox", vec![0.into(), 0.into(), 595.into(), 842.into()]);
+    let page_id = pdf.add_object(page);
+    let mut pages = Dictionary::new();
+    pages.set("Type", Object::Name(b"Pages".to_vec()));
+    pages.set("Kids", vec![page_id.into()]);
+    pages.set("Count", 1);
+    pdf.objects.insert(pages_id, Object::Dictionary(pages));
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+    catalog.set("Pages", pages_id);
+    let catalog_id = pdf.add_object(catalog);
+    pdf.trailer.set("Root", catalog_id);
+    let mut data = Vec::new();
+    pdf.save_to(&mut data).unwrap();
+
+    let document = |data: Vec<u8>| DocumentContent {
+        data,
+        media_type: "application/pdf".to_string(),
+        filename: Some("blank.pdf".to_string()),
+    };
+    let (mut texts, mut images) = (Vec::new(), Vec::new());
+    let error = add_document(&document(data), &mut texts, &mut images).unwrap_err();
+    assert!(error.to_string().contains("blank.pdf"));
+    assert!(texts.is_empty() && images.is_empty());
+    // a malformed PDF is an error rather than a panic
+    let malformed = b"%PDF-1.5\n1 0 obj\n<< /Type /Catalog".to_vec();
+    assert!(add_document(&document(malformed), &mut texts, &mut images).is_err());
+}
+
+#[test]
+fn test_history_ollama() {
+    let user = |text: &str| Message {
+        role: "user".to_string(),
+        content: vec![Content {
+            ctx: ContentTypeInner::Text(TextContent::new(text).unwrap()),
+            cache_control: None,
+        }],
+    };
+    let answer = |text: &str, context: Option<Vec<i64>>| LLMResponse {
+        content: Some(vec![ResponseAnthropic {
+            content_type: "text".to_string(),
+            text: text.to_string(),
+        }]),
+        context,
+        ..Default::default()
+    };
+    let mut request = OllamaRequest::new(
+        "http://localhost:11434",
+        "qwen2.5vl",
+        vec![user("What is your name?")],
+        Some("Be brief"),
+        None,
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+
+    // without a context (e.g. after chat mode) the whole history is rendered
+    request.add_response(answer("Qwen", None));
+    request.add_message(user("Again in Chinese"));
+    let generate = ConvertedOllamaRequest::from_ollama_request(request.clone(), false).unwrap();
+    assert_eq!(
+        generate.prompt,
+        "Be brief\n\nThe following is my message:\n\nUser: What is your name?\n\nAssistant: Qwen\n\nUser: Again in Chinese\n\nAssistant:"
+    );
+    assert!(generate.context.is_none());
+
+    // with the context of the last response only the new message is sent
+    request.add_response(answer("通义千问", Some(vec![1, 2, 3])));
+    request.add_message(user("Thanks"));
+    let generate = ConvertedOllamaRequest::from_ollama_request(request.clone(), false).unwrap();
+    assert_eq!(generate.prompt, "Thanks");
+    assert_eq!(generate.context, Some(vec![1, 2, 3]));
+
+    let chat = OllamaChatRequest::from_ollama_request(request.clone(), false).unwrap();
+    assert_eq!(chat.messages[0].role, "system");
+    assert_eq!(chat.messages.len(), 6);
+
+    request.add_response(answer("Bitte", Some(vec![4])));
+    assert!(ConvertedOllamaRequest::from_ollama_request(request, false).is_err());
+}
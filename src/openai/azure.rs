@@ -0,0 +1,358 @@
+use anyhow::{Result, anyhow, bail};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use crate::client;
+use crate::error::{ErrorKind, LocalError};
+
+pub const AZURE_API_VERSION: &str = "2025-04-01-preview";
+
+static SETTINGS: RwLock<Option<AzureSettings>> = RwLock::new(None);
+
+/// The resource, deployments and credentials of Azure OpenAI, used for every OpenAIRequest
+/// instead of the API of OpenAI
+#[derive(Clone)]
+#[pyclass(dict, frozen)]
+pub struct AzureSettings {
+    #[pyo3(get)]
+    pub endpoint: String, // e.g. "https://my-resource.openai.azure.com"
+    #[pyo3(get)]
+    pub api_version: String, // "v1" for the v1 API, which takes no api-version
+    #[pyo3(get)]
+    pub deployments: BTreeMap<String, String>, // deployment name to model name
+    pub api_key: Option<String>, // sent as `api-key`, None for AZURE_OPENAI_API_KEY, not readable
+    pub ad_token: Option<String>, // a Microsoft Entra ID token, sent as bearer token, not readable
+    ad_token_provider: Option<Arc<Py<PyAny>>>, // called for a fresh Entra ID token per request
+}
+
+#[pymethods]
+impl AzureSettings {
+    #[new]
+    #[pyo3(signature = (endpoint=None, api_version=None, deployments=None, api_key=None, ad_token=None, ad_token_provider=None))]
+    fn new(
+        endpoint: Option<String>,
+        api_version: Option<String>,
+        deployments: Option<BTreeMap<String, String>>,
+        api_key: Option<String>,
+        ad_token: Option<String>,
+        ad_token_provider: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let Some(endpoint) = endpoint.or_else(|| std::env::var("AZURE_OPENAI_ENDPOINT").ok())
+        else {
+            return Err(PyValueError::new_err(
+                "endpoint or the AZURE_OPENAI_ENDPOINT environment variable must be set",
+            ));
+        };
+        reqwest::Url::parse(&endpoint)
+            .map_err(|e| PyValueError::new_err(format!("Invalid endpoint {}: {}", endpoint, e)))?;
+        if [
+            api_key.is_some(),
+            ad_token.is_some(),
+            ad_token_provider.is_some(),
+        ]
+        .iter()
+        .filter(|given| **given)
+        .count()
+            > 1
+        {
+            return Err(PyValueError::new_err(
+                "Only one of api_key, ad_token and ad_token_provider can be given",
+            ));
+        }
+        if let Some(provider) = &ad_token_provider
+            && !provider.is_callable()
+        {
+            return Err(PyValueError::new_err("ad_token_provider must be callable"));
+        }
+        Ok(Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            api_version: api_version.unwrap_or_else(|| {
+                std::env::var("AZURE_OPENAI_API_VERSION").unwrap_or(AZURE_API_VERSION.to_string())
+            }),
+            deployments: deployments.unwrap_or_default(),
+            api_key,
+            ad_token,
+            ad_token_provider: ad_token_provider.map(|provider| Arc::new(provider.unbind())),
+        })
+    }
+
+    #[getter]
+    fn ad_token_provider(&self, py: Python<'_>) -> Option<Py<PyAny>> {
+        self.ad_token_provider
+            .as_ref()
+            .map(|provider| provider.clone_ref(py))
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        // the credentials are not shown, reprs end up in logs
+        Ok(format!(
+            "AzureSettings<endpoint={},api_version={},deployments={:?},api_key={},ad_token={}>",
+            self.endpoint,
+            self.api_version,
+            self.deployments,
+            mask(&self.api_key),
+            mask(&self.ad_token)
+        ))
+    }
+}
+
+fn mask(secret: &Option<String>) -> &'static str {
+    match secret {
+        Some(_) => "***",
+        None => "None",
+    }
+}
+
+impl std::fmt::Debug for AzureSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AzureSettings")
+            .field("endpoint", &self.endpoint)
+            .field("api_version", &self.api_version)
+            .field("deployments", &self.deployments)
+            .field("api_key", &mask(&self.api_key))
+            .field("ad_token", &mask(&self.ad_token))
+            .field("ad_token_provider", &self.ad_token_provider.is_some())
+            .finish()
+    }
+}
+
+impl AzureSettings {
+    /// The settings of a request whose endpoint is on Azure, with the credentials and version
+    /// of the environment
+    fn from_endpoint(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            api_version: std::env::var("AZURE_OPENAI_API_VERSION")
+                .unwrap_or(AZURE_API_VERSION.to_string()),
+            deployments: BTreeMap::new(),
+            api_key: None,
+            ad_token: None,
+            ad_token_provider: None,
+        }
+    }
+
+    /// The URL of the Responses API, an endpoint which already is one is taken as given
+    pub(crate) fn url(&self) -> String {
+        if self.endpoint.contains("/responses") {
+            return match self.endpoint.contains("api-version=") || self.api_version == "v1" {
+                true => self.endpoint.clone(),
+                false => format!("{}?api-version={}", self.endpoint, self.api_version),
+            };
+        }
+        let root = self
+            .endpoint
+            .trim_end_matches("/openai/v1")
+            .trim_end_matches("/openai");
+        match self.api_version.as_str() {
+            "v1" => format!("{}/openai/v1/responses", root),
+            version => format!("{}/openai/responses?api-version={}", root, version),
+        }
+    }
+
+    /// The deployment to send a request for `model` to, `model` itself if it is a deployment or
+    /// not in `deployments`
+    pub(crate) fn deployment<'a>(&'a self, model: &'a str) -> &'a str {
+        if self.deployments.contains_key(model) {
+            return model;
+        }
+        self.deployments
+            .iter()
+            .find(|(_, deployed)| deployed.as_str() == model)
+            .map_or(model, |(deployment, _)| deployment)
+    }
+
+    /// The model behind a deployment, e.g. for its tokenizer
+    pub(crate) fn model<'a>(&'a self, deployment: &'a str) -> &'a str {
+        self.deployments
+            .get(deployment)
+            .map_or(deployment, |model| model)
+    }
+
+    /// The settings with the token of `ad_token_provider` as `ad_token`
+    ///
+    /// The provider is Python, so this is called before the request future is started (where the
+    /// GIL is held) rather than in it, which would block a worker of the runtime on the GIL
+    fn with_token(self) -> Result<Self> {
+        let Some(provider) = &self.ad_token_provider else {
+            return Ok(self);
+        };
+        let token =
+            crate::runtime::attach_unless_shutdown(|py| provider.call0(py)?.extract::<String>(py))
+                .ok_or_else(|| anyhow!("The interpreter is shutting down"))?
+                .map_err(|e| anyhow!("ad_token_provider failed: {}", e))?;
+        Ok(Self {
+            ad_token: Some(token),
+            ad_token_provider: None,
+            ..self
+        })
+    }
+
+    /// The authentication header, an Entra ID token or else the API key
+    pub(crate) fn auth_header(&self) -> Result<(&'static str, String)> {
+        if self.ad_token_provider.is_some() {
+            bail!("The token of ad_token_provider must be resolved before the request is sent");
+        }
+        if let Some(token) = &self.ad_token {
+            return Ok(("Authorization", format!("Bearer {}", token)));
+        }
+        let api_key = match &self.api_key {
+            Some(api_key) => api_key.clone(),
+            None => std::env::var("AZURE_OPENAI_API_KEY")
+                .ok()
+                .filter(|key| !key.is_empty())
+                .ok_or_else(|| {
+                    LocalError::new(
+                        ErrorKind::Authentication,
+                        "AZURE_OPENAI_API_KEY environment variable must be set",
+                    )
+                })?,
+        };
+        Ok(("api-key", api_key))
+    }
+}
+
+/// The Azure settings of a request to `endpoint` (None for the default one): of the current
+/// `GoldenAIClient`, else the ones set by `set_azure_settings`, else of the environment for an
+/// endpoint on Azure; None for the API of OpenAI
+///
+/// The credentials of configured settings are only sent to the origin of their own endpoint, a
+/// custom domain or a gateway in front of Azure is taken by setting it as that endpoint
+pub(crate) fn settings(endpoint: Option<&str>) -> Option<AzureSettings> {
+    let configured = client::current()
+        .and_then(|client| client.azure_openai.clone())
+        .or_else(|| SETTINGS.read().unwrap().clone());
+    let Some(endpoint) = endpoint else {
+        return configured;
+    };
+    match configured {
+        Some(settings)
+            if origin(endpoint).is_some_and(|o| Some(o) == origin(&settings.endpoint)) =>
+        {
+            Some(AzureSettings {
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+                ..settings
+            })
+        }
+        _ if is_azure(endpoint) => Some(AzureSettings::from_endpoint(endpoint)),
+        _ => None,
+    }
+}
+
+/// The `settings` of a request to `endpoint` with the token of their `ad_token_provider`,
+/// resolved before the request is sent
+pub(crate) fn resolve(endpoint: Option<&str>) -> Result<Option<AzureSettings>> {
+    settings(endpoint)
+        .map(AzureSettings::with_token)
+        .transpose()
+}
+
+fn origin(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .map(|url| url.origin().ascii_serialization())
+}
+
+/// An endpoint of an Azure resource, e.g. `https://my-resource.openai.azure.com`
+fn is_azure(endpoint: &str) -> bool {
+    reqwest::Url::parse(endpoint)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.ends_with(".azure.com")))
+        .unwrap_or(false)
+}
+
+/// Send every following OpenAIRequest to Azure OpenAI, None restores the API of OpenAI
+#[pyfunction]
+#[pyo3(signature = (settings=None))]
+pub fn set_azure_settings(settings: Option<AzureSettings>) {
+    *SETTINGS.write().unwrap() = settings;
+}
+
+#[test]
+fn test_azure_settings() {
+    let mut azure = AzureSettings::from_endpoint("https://my-resource.openai.azure.com/");
+    azure.api_version = "2025-04-01-preview".to_string();
+    azure.deployments = BTreeMap::from([("prod-chat".to_string(), "gpt-4.1".to_string())]);
+    assert_eq!(
+        azure.url(),
+        "https://my-resource.openai.azure.com/openai/responses?api-version=2025-04-01-preview"
+    );
+    assert_eq!(azure.deployment("gpt-4.1"), "prod-chat");
+    assert_eq!(azure.deployment("prod-chat"), "prod-chat");
+    assert_eq!(azure.deployment("gpt-5"), "gpt-5");
+    assert_eq!(azure.model("prod-chat"), "gpt-4.1");
+
+    azure.api_version = "v1".to_string();
+    assert_eq!(
+        azure.url(),
+        "https://my-resource.openai.azure.com/openai/v1/responses"
+    );
+    azure.endpoint = "https://x.cognitiveservices.azure.com/openai/responses".to_string();
+    azure.api_version = "2025-03-01-preview".to_string();
+    assert_eq!(
+        azure.url(),
+        "https://x.cognitiveservices.azure.com/openai/responses?api-version=2025-03-01-preview"
+    );
+
+    azure.ad_token = Some("entra".to_string());
+    assert_eq!(
+        azure.auth_header().unwrap(),
+        ("Authorization", "Bearer entra".to_string())
+    );
+    azure.ad_token = None;
+    azure.api_key = Some("key".to_string());
+    assert_eq!(azure.auth_header().unwrap(), ("api-key", "key".to_string()));
+
+    assert!(settings(Some("https://api.openai.com/v1/responses")).is_none());
+    let client = client::GoldenAIClient {
+        azure_openai: Some(azure.clone()),
+        ..Default::default()
+    };
+    let [openai, gateway, resource, other_resource] =
+        crate::runtime::runtime().block_on(client::scope(Some(Arc::new(client)), async {
+            [
+                "https://api.openai.com/v1/responses",
+                "https://llm-gateway.example.com/openai/responses",
+                "https://x.cognitiveservices.azure.com/openai/v1/responses",
+                "https://other.openai.azure.com",
+            ]
+            .map(|endpoint| settings(Some(endpoint)))
+        }));
+    // the configured key is only sent to the origin of the configured endpoint
+    assert!(openai.is_none());
+    assert!(gateway.is_none());
+    assert_eq!(resource.unwrap().api_key.as_deref(), Some("key"));
+    assert!(other_resource.unwrap().api_key.is_none());
+    assert!(settings(Some("https://evil.example.com/azure/responses")).is_none());
+    assert_eq!(
+        settings(Some("https://my-resource.openai.azure.com"))
+            .unwrap()
+            .url(),
+        format!(
+            "https://my-resource.openai.azure.com/openai/responses?api-version={}",
+            std::env::var("AZURE_OPENAI_API_VERSION").unwrap_or(AZURE_API_VERSION.to_string())
+        )
+    );
+
+    let repr = format!("{:?} {}", azure, azure.__repr__().unwrap());
+    assert!(!repr.contains("\"key\"") && repr.contains("api_key=***"));
+}
+
+#[test]
+fn test_ad_token_provider() {
+    Python::initialize();
+    let provider = Python::attach(|py| py.eval(c"lambda: 'entra'", None, None).unwrap().unbind());
+    let azure = AzureSettings {
+        ad_token_provider: Some(Arc::new(provider)),
+        ..AzureSettings::from_endpoint("https://my-resource.openai.azure.com")
+    };
+    // the provider is only called when the request is prepared, never by the request itself
+    assert!(azure.auth_header().is_err());
+    let azure = azure.with_token().unwrap();
+    assert!(azure.ad_token_provider.is_none());
+    assert_eq!(
+        azure.auth_header().unwrap(),
+        ("Authorization", "Bearer entra".to_string())
+    );
+}
@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use crate::SupportedModels;
+use crate::anthropic::structs::ResponseAnthropic;
+use crate::message::{ContentTypeInner, Message};
+use crate::response::{LLMResponse, Usage};
+use crate::tools::{Tool, ToolCall};
+
+// The Chat Completions API of OpenAI, which is also spoken by Mistral and most self-hosted servers
+
+/// Body of `/chat/completions`
+#[derive(Serialize, Debug)]
+pub struct ChatCompletionsRequest {
+    pub model: String,
+    pub messages: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<Value>,
+    #[serde(flatten)]
+    pub params: Map<String, Value>, // of a GenerationConfig
+}
+
+impl ChatCompletionsRequest {
+    pub fn new(
+        model: &str,
+        system: Option<&str>,
+        messages: &[Message],
+        max_tokens: Option<u32>,
+        tools: &[Tool],
+        tool_choice: Option<&str>,
+    ) -> Self {
+        Self {
+            model: model.to_string(),
+            messages: to_chat_messages(system, messages),
+            max_tokens,
+            tools: tools.iter().map(Tool::to_chat_completions).collect(),
+            tool_choice: tool_choice.map(|tool_choice| match tool_choice {
+                "auto" | "none" => json!(tool_choice),
+                "required" | "any" => json!("required"),
+                name => json!({"type": "function", "function": {"name": name}}),
+            }),
+            response_format: None,
+            stream: false,
+            stream_options: None,
+            params: Map::new(),
+        }
+    }
+}
+
+/// Tool calls are part of the assistant message, every tool result is a message of its own
+fn to_chat_messages(system: Option<&str>, messages: &[Message]) -> Vec<Value> {
+    let mut chat_messages: Vec<Value> = Vec::new();
+    if let Some(system) = system {
+        chat_messages.push(json!({"role": "system", "content": system}));
+    }
+
+    for message in messages {
+        let mut parts: Vec<Value> = Vec::new();
+        let mut tool_calls: Vec<Value> = Vec::new();
+        for each_content in &message.content {
+            match &each_content.ctx {
+                ContentTypeInner::Text(text) => {
+                    parts.push(json!({"type": "text", "text": text.text}))
+                }
+                ContentTypeInner::Document(doc) => parts.push(match doc.is_image() {
+                    true => json!({"type": "image_url", "image_url": {"url": doc.data_uri()}}),
+                    false => json!({"type": "file", "file": {
+                        "filename": doc.filename.as_deref().unwrap_or("document"),
+                        "file_data": doc.data_uri(),
+                    }}),
+                }),
+                ContentTypeInner::ToolCall(call) => tool_calls.push(call.to_chat_completions()),
+                ContentTypeInner::ToolResult(result) => {
+                    let mut tool_message = json!({
+                        "role": "tool",
+                        "tool_call_id": result.tool_call_id,
+                        "content": result.content,
+                    });
+                    if let Some(name) = &result.name {
+                        tool_message["name"] = json!(name);
+                    }
+                    chat_messages.push(tool_message);
+                }
+                ContentTypeInner::Thinking(_) => {}
+            }
+        }
+
+        if parts.is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+        // an assistant message of only tool calls has no content rather than an empty one
+        let content = match parts.is_empty() {
+            true => Value::Null,
+            false => Value::Array(parts),
+        };
+        let mut chat_message = json!({"role": message.role, "content": content});
+        if !tool_calls.is_empty() {
+            chat_message["tool_calls"] = Value::Array(tool_calls);
+        }
+        chat_messages.push(chat_message);
+    }
+    chat_messages
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct ChatCompletionsMessage {
+    pub role: Option<String>,
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<Value>>, // null rather than missing on Mistral
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChatCompletionsChoice {
+    #[serde(default)]
+    pub message: ChatCompletionsMessage,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChatCompletionsResponse {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub choices: Vec<ChatCompletionsChoice>,
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+impl ChatCompletionsResponse {
+    /// The model of the request is kept, servers often answer with a dated or local name
+    pub fn to_llm_response(self, model: SupportedModels) -> LLMResponse {
+        let choice = self.choices.into_iter().next();
+        let message = choice
+            .as_ref()
+            .map(|choice| choice.message.clone())
+            .unwrap_or_default();
+
+        LLMResponse {
+            id: self.id,
+            model,
+            response_type: "chat.completion".to_string(),
+            usage: self.usage,
+            role: Some(message.role.unwrap_or("assistant".to_string())),
+            content: Some(vec![ResponseAnthropic {
+                content_type: "text".to_string(),
+                text: message.content.unwrap_or_default(),
+            }]),
+            stop_reason: choice.and_then(|choice| choice.finish_reason),
+            tool_calls: message
+                .tool_calls
+                .iter()
+                .flatten()
+                .filter_map(ToolCall::from_chat_completions)
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[test]
+fn test_chat_completions_request() {
+    use crate::message::{Content, TextContent, ToolResult};
+
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "get_weather".to_string(),
+        arguments: json!({"city": "Berlin"}),
+    };
+    let messages = vec![
+        Message {
+            role: "user".to_string(),
+            content: vec![Content {
+                ctx: ContentTypeInner::Text(TextContent::new("Weather in Berlin?").unwrap()),
+                cache_control: None,
+            }],
+        },
+        Message {
+            role: "assistant".to_string(),
+            content: vec![Content {
+                ctx: ContentTypeInner::ToolCall(call.clone()),
+                cache_control: None,
+            }],
+        },
+        Message {
+            role: "user".to_string(),
+            content: vec![Content {
+                ctx: ContentTypeInner::ToolResult(ToolResult::new(
+                    "call_1",
+                    "Sunny",
+                    Some("get_weather"),
+                    false,
+                )),
+                cache_control: None,
+            }],
+        },
+    ];
+
+    let body = serde_json::to_value(ChatCompletionsRequest::new(
+        "mistral-small-latest",
+        Some("Be brief"),
+        &messages,
+        None,
+        &[],
+        Some("get_weather"),
+    ))
+    .unwrap();
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert_eq!(
+        body["messages"][1]["content"][0]["text"],
+        "Weather in Berlin?"
+    );
+    assert_eq!(
+        body["messages"][2]["tool_calls"][0]["function"]["arguments"],
+        "{\"city\":\"Berlin\"}"
+    );
+    assert!(body["messages"][2]["content"].is_null());
+    assert_eq!(body["messages"][3]["role"], "tool");
+    assert_eq!(body["messages"][3]["name"], "get_weather");
+    assert_eq!(body["tool_choice"]["function"]["name"], "get_weather");
+
+    let unnamed = Message {
+        role: "user".to_string(),
+        content: vec![Content {
+            ctx: ContentTypeInner::ToolResult(ToolResult::new("call_1", "Sunny", None, false)),
+            cache_control: None,
+        }],
+    };
+    let body = to_chat_messages(None, &[unnamed]);
+    assert!(body[0].get("name").is_none());
+
+    let response: ChatCompletionsResponse = serde_json::from_value(json!({
+        "id": "cmpl-1",
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": null, "tool_calls": [
+            {"id": "call_2", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}}
+        ]}, "finish_reason": "tool_calls"}],
+        "usage": {"prompt_tokens": 20, "completion_tokens": 5, "total_tokens": 25},
+    }))
+    .unwrap();
+    let response = response.to_llm_response(SupportedModels::MistralSmallLatest);
+    assert_eq!(response.usage.input_tokens, 20);
+    assert_eq!(response.tool_calls[0].arguments["city"], "Paris");
+}
@@ -1,38 +1,69 @@
-use anyhow::{Result, anyhow};
-use reqwest;
-use std::env;
+use anyhow::Result;
+use reqwest::Response;
+use tiktoken_rs::tokenizer::get_tokenizer;
 use tiktoken_rs::{ChatCompletionRequestMessage, num_tokens_from_messages};
 
-use crate::message::ContentTypeInner;
-use crate::openai::structs::OpenAIRequest;
+use crate::SupportedModels;
+use crate::error::{ErrorKind, LocalError};
+use crate::generation::with_params;
+use crate::message::{ContentTypeInner, Message};
+use crate::openai::azure::{self, AzureSettings};
+use crate::openai::completions::ChatCompletionsResponse;
+use crate::openai::structs::{OpenAICompatRequest, OpenAIRequest};
 use crate::response::LLMResponse;
+use crate::retry::send_with_retry;
+use crate::runtime::{client, runtime};
 
-#[tokio::main]
-pub async fn get_response_openai(request_body: OpenAIRequest) -> Result<LLMResponse> {
-    request_openai(request_body).await
+pub fn get_response_openai(request_body: OpenAIRequest) -> Result<LLMResponse> {
+    let azure = azure::resolve(request_body.endpoint.as_deref())?;
+    runtime().block_on(request_openai(request_body, azure))
 }
 
 pub fn get_count_tokens_openai(request_body: OpenAIRequest) -> Result<u32> {
     count_tokens_openai(request_body)
 }
 
-async fn request_openai(request_body: OpenAIRequest) -> Result<LLMResponse> {
+/// `azure` are the settings of `azure::resolve`, resolved before the future is started
+pub(crate) async fn request_openai(
+    request_body: OpenAIRequest,
+    azure: Option<AzureSettings>,
+) -> Result<LLMResponse> {
+    let response = post_openai(&request_body, azure.as_ref()).await?;
+    // let response_text = response.text().await?;
+    // println!("Raw response: {}", response_text);
+    // let response: LLMResponse = serde_json::from_str(&response_text)?;
+    let response = LLMResponse::from_json(response.json().await?)?;
+    Ok(response)
+}
+
+/// Send the request with `stream` enabled and hand back the raw SSE response
+pub(crate) async fn request_openai_stream(
+    mut request_body: OpenAIRequest,
+    azure: Option<AzureSettings>,
+) -> Result<Response> {
+    request_body.stream = Some(true);
+    post_openai(&request_body, azure.as_ref()).await
+}
+
+async fn post_openai(
+    request_body: &OpenAIRequest,
+    azure: Option<&AzureSettings>,
+) -> Result<Response> {
+    if let Some(azure) = azure {
+        return post_azure(request_body, azure).await;
+    }
     let endpoint = match &request_body.endpoint {
         Some(url) => url.clone(),
         None => {
-            // use OpenAI API by default
-            crate::OPENAI_API_URL.to_string()
+            // use OpenAI API (or the base URL of the client) by default
+            format!(
+                "{}/responses",
+                crate::client::base_url("openai", crate::OPENAI_API_URL)
+            )
         }
     };
 
-    let api_key = match if endpoint.contains("azure") {
-        env::var("AZURE_OPENAI_API_KEY")
-    } else {
-        env::var("OPENAI_API_KEY")
-    } {
-        Ok(key) => key,
-        Err(_) => return Err(anyhow!("OpenAI API key must be set")),
-    };
+    let api_key = crate::client::api_key("openai", "OPENAI_API_KEY")?;
 
     // For debugging (review the request body)
     // let json_string = serde_json::to_string_pretty(&request_body)
@@ -40,41 +71,130 @@ async fn request_openai(request_body: OpenAIRequest) -> Result<LLMResponse> {
     // println!("{}", json_string);
     // return Err(anyhow!("Debugging"));
 
-    let client = reqwest::Client::new();
-    let response = client
+    let request = client("openai", &endpoint)?
         .post(endpoint)
         .header("content-type", "application/json")
         .header("Authorization", format!("Bearer {}", api_key))
-        .json(&request_body)
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        // let response_text = response.text().await?;
-        // println!("Raw response: {}", response_text);
-        // let response: LLMResponse = serde_json::from_str(&response_text)?;
-        let response: LLMResponse = response.json().await?;
-        Ok(response)
-    } else {
-        let err_status = response.status();
-        let error_text = response.text().await?;
-        Err(anyhow!(
-            "Error: HTTP {}, Response: {}",
-            err_status,
-            error_text
-        ))
+        .json(&to_body(request_body)?);
+
+    send_with_retry("openai", request).await
+}
+
+/// The same request to the deployment on Azure OpenAI
+async fn post_azure(request_body: &OpenAIRequest, azure: &AzureSettings) -> Result<Response> {
+    let mut body = to_body(request_body)?;
+    body["model"] = azure.deployment(request_body.model.to_str()).into();
+    let (auth_header, auth_value) = azure.auth_header()?;
+
+    let url = azure.url();
+    let request = client("azure_openai", &url)?
+        .post(&url)
+        .header("content-type", "application/json")
+        .header(auth_header, auth_value)
+        .json(&body);
+
+    send_with_retry("azure_openai", request).await
+}
+
+fn to_body(request_body: &OpenAIRequest) -> Result<serde_json::Value> {
+    let params = request_body
+        .generation_config
+        .as_ref()
+        .map(|config| config.to_openai(&request_body.model))
+        .transpose()?;
+    with_params(request_body, params)
+}
+
+pub fn get_response_openai_compat(request_body: OpenAICompatRequest) -> Result<LLMResponse> {
+    runtime().block_on(request_openai_compat(request_body))
+}
+
+pub fn get_count_tokens_openai_compat(request_body: OpenAICompatRequest) -> Result<u32> {
+    count_tokens_openai_compat(request_body)
+}
+
+pub(crate) async fn request_openai_compat(
+    request_body: OpenAICompatRequest,
+) -> Result<LLMResponse> {
+    let response = post_openai_compat(&request_body, false).await?;
+    let response: ChatCompletionsResponse = response.json().await?;
+    let model = SupportedModels::from_str(&request_body.model)?;
+    Ok(response.to_llm_response(model))
+}
+
+/// Send the request with `stream` enabled and hand back the raw SSE response
+pub(crate) async fn request_openai_compat_stream(
+    request_body: OpenAICompatRequest,
+) -> Result<Response> {
+    post_openai_compat(&request_body, true).await
+}
+
+/// There is no common endpoint to count tokens, so only the models of a known tokenizer (e.g. the
+/// models of OpenAI behind a gateway) are counted, locally
+pub(crate) fn count_tokens_openai_compat(request_body: OpenAICompatRequest) -> Result<u32> {
+    if get_tokenizer(&request_body.model).is_none() {
+        return Err(LocalError::new(
+            ErrorKind::InvalidRequest,
+            format!(
+                "Counting tokens is not supported for {}, its tokenizer is unknown",
+                request_body.model
+            ),
+        )
+        .into());
     }
+    num_tokens(
+        &request_body.model,
+        request_body.system.as_deref(),
+        &request_body.messages,
+    )
 }
 
-fn count_tokens_openai(request_body: OpenAIRequest) -> Result<u32> {
-    let api_key = env::var("OPENAI_API_KEY").unwrap_or("".to_string());
-    if api_key.is_empty() {
-        return Err(anyhow!("OPENAI_API_KEY environment variable must be set"));
+async fn post_openai_compat(request_body: &OpenAICompatRequest, stream: bool) -> Result<Response> {
+    let url = request_body.url();
+    let mut request = client("openai_compat", &url)?
+        .post(&url)
+        .header("content-type", "application/json")
+        .json(&request_body.to_chat_completions(stream)?);
+    // self-hosted servers often run without authentication
+    if let Some(auth_value) = request_body.auth_value() {
+        request = request.header(&request_body.auth_header, auth_value);
     }
+    send_with_retry("openai_compat", request).await
+}
+
+fn count_tokens_openai(request_body: OpenAIRequest) -> Result<u32> {
+    // a deployment on Azure is counted with the tokenizer of its model
+    let azure = azure::settings(request_body.endpoint.as_deref());
+    let model = match &azure {
+        Some(azure) => azure.model(request_body.model.to_str()),
+        None => request_body.model.to_str(),
+    };
+    // newer models than the tokenizers know of all use o200k_base
+    let model = match get_tokenizer(model) {
+        Some(_) => model,
+        None => "gpt-4o",
+    };
+
+    num_tokens(
+        model,
+        request_body.instructions.as_deref(),
+        &request_body.input,
+    )
+}
 
+/// The tokens of the system prompt and the messages with the tokenizer of `model`
+fn num_tokens(model: &str, system: Option<&str>, input: &[Message]) -> Result<u32> {
     let mut messages: Vec<ChatCompletionRequestMessage> = Vec::new();
+    if let Some(instructions) = system {
+        messages.push(ChatCompletionRequestMessage {
+            role: "system".to_string(),
+            content: Some(instructions.to_string()),
+            name: None,
+            function_call: None,
+        });
+    }
 
-    for each_message in request_body.input.iter() {
+    for each_message in input.iter() {
         let message = ChatCompletionRequestMessage {
             role: each_message.role.clone(),
             content: each_message
@@ -85,6 +205,9 @@ fn count_tokens_openai(request_body: OpenAIRequest) -> Result<u32> {
                     ContentTypeInner::Document(_document) => {
                         Some("".to_string()) // Document (PDF) is having problem calculate correct token
                     }
+                    ContentTypeInner::ToolCall(call) => Some(call.arguments.to_string()),
+                    ContentTypeInner::ToolResult(result) => Some(result.content.clone()),
+                    ContentTypeInner::Thinking(_) => Some("".to_string()),
                 })
                 .collect(),
             name: None,
@@ -93,7 +216,7 @@ fn count_tokens_openai(request_body: OpenAIRequest) -> Result<u32> {
         messages.push(message);
     }
 
-    let max_tokens = num_tokens_from_messages(request_body.model.to_str(), &messages)?;
+    let max_tokens = num_tokens_from_messages(model, &messages)?;
 
     Ok(max_tokens as u32)
 }
@@ -116,6 +239,7 @@ async fn test_request_openai() {
                         content_type: "input_text".to_string(),
                         text: "What does the document say?".to_string(),
                     }),
+                    cache_control: None,
                 },
                 Content {
                     ctx: ContentTypeInner::Document(
@@ -125,6 +249,7 @@ async fn test_request_openai() {
                         )
                         .unwrap(),
                     ),
+                    cache_control: None,
                 },
             ],
         }],
@@ -132,14 +257,21 @@ async fn test_request_openai() {
         // None, // endpoint
         Some("https://guang-meb38l00-swedencentral.cognitiveservices.azure.com"), // endpoint
         None, // max_output_tokens
-    );
+        None, // tools
+        None, // tool_choice
+        None, // generation_config
+        None, // reasoning_effort
+        None, // reasoning_summary
+    )
+    .unwrap();
 
     // println!(
     //     "Input tokens: {}",
     //     count_tokens_openai(request_body.clone()).unwrap()
     // );
 
-    let response = request_openai(request_body.clone()).await;
+    let azure = azure::resolve(request_body.endpoint.as_deref()).unwrap();
+    let response = request_openai(request_body.clone(), azure.clone()).await;
     match response {
         Ok(res) => {
             println!("{}", res);
@@ -152,13 +284,14 @@ async fn test_request_openai() {
                         content_type: "input_text".to_string(),
                         text: "Please answer the same question but in English again".to_string(),
                     }),
+                    cache_control: None,
                 }],
             });
             // println!(
             //     "Input tokens 2: {}",
             //     count_tokens_openai(request_body.clone()).unwrap()
             // );
-            let new_response = request_openai(request_body).await;
+            let new_response = request_openai(request_body, azure).await;
             match new_response {
                 Ok(new_res) => {
                     println!("{}", new_res);
@@ -170,3 +303,38 @@ async fn test_request_openai() {
         Err(e) => println!("Error: {}", e),
     }
 }
+
+#[test]
+fn test_count_tokens_openai_compat() {
+    use crate::message::{Content, TextContent};
+
+    let messages = vec![Message {
+        role: "user".to_string(),
+        content: vec![Content {
+            ctx: ContentTypeInner::Text(TextContent::new("Hello, how are you?").unwrap()),
+            cache_control: None,
+        }],
+    }];
+    let request = |model: &str| {
+        OpenAICompatRequest::new(
+            "http://localhost:8000/v1",
+            model,
+            messages.clone(),
+            Some("Be brief"),
+            None,
+            "Authorization",
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    };
+    // counted locally, nothing is sent to the server
+    assert!(count_tokens_openai_compat(request("gpt-4o-mini")).unwrap() > 0);
+    let error = count_tokens_openai_compat(request("llama3.2:3b")).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<LocalError>().unwrap().kind,
+        ErrorKind::InvalidRequest
+    );
+}
@@ -1,11 +1,15 @@
 use crate::SupportedModels;
+use crate::generation::GenerationConfig;
 use crate::message::{Content, ContentTypeInner, Message, TextContent};
+use crate::openai::completions::ChatCompletionsRequest;
 use crate::response::LLMResponse;
-use pyo3::IntoPyObjectExt;
+use crate::schema::Json;
+use crate::tools::{Tool, serialize_openai_tool_choice, serialize_openai_tools};
 use pyo3::prelude::*;
-use pythonize::pythonize;
 use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Error, SerializeSeq, Serializer};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use std::fmt;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -24,29 +28,129 @@ impl Default for OpenAIReasoning {
     }
 }
 
+impl OpenAIReasoning {
+    fn new(effort: Option<&str>, summary: Option<&str>) -> PyResult<Self> {
+        if let Some(effort) = effort
+            && !["minimal", "low", "medium", "high"].contains(&effort)
+        {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "reasoning_effort must be \"minimal\", \"low\", \"medium\" or \"high\", got {:?}",
+                effort
+            )));
+        }
+        if let Some(summary) = summary
+            && !["auto", "concise", "detailed"].contains(&summary)
+        {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "reasoning_summary must be \"auto\", \"concise\" or \"detailed\", got {:?}",
+                summary
+            )));
+        }
+        let default = Self::default();
+        Ok(Self {
+            effort: effort.map(|s| s.to_string()).or(default.effort),
+            summary: summary.map(|s| s.to_string()),
+        })
+    }
+}
+
 #[derive(Serialize, Clone, Debug, Default)]
 #[pyclass(dict, get_all, set_all, subclass)]
 pub struct OpenAIRequest {
     pub(crate) model: SupportedModels,
+    #[serde(serialize_with = "serialize_input")]
     pub(crate) input: Vec<Message>,
     pub(crate) instructions: Option<String>,
     #[serde(skip)]
     pub(crate) endpoint: Option<String>,
     pub(crate) reasoning: Option<OpenAIReasoning>,
     pub(crate) max_output_tokens: Option<u32>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_openai_tools"
+    )]
+    pub(crate) tools: Vec<Tool>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_openai_tool_choice"
+    )]
+    pub(crate) tool_choice: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) text: Option<Json>, // structured output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) stream: Option<bool>,
+    #[serde(skip)]
+    pub(crate) generation_config: Option<GenerationConfig>, // added to the body by post_openai
+}
+
+// Tool calls and tool results are items of their own in the input of the Responses API,
+// so messages are split around them (keeping the order)
+fn serialize_input<S>(input: &[Message], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    #[derive(Serialize)]
+    struct InputMessage<'a> {
+        role: &'a str,
+        content: Vec<Value>,
+    }
+
+    let mut seq = serializer.serialize_seq(None)?;
+    for message in input {
+        let mut content: Vec<Value> = Vec::new();
+        for each_content in &message.content {
+            let item = match &each_content.ctx {
+                ContentTypeInner::ToolCall(call) => call.to_openai(),
+                ContentTypeInner::ToolResult(result) => json!({
+                    "type": "function_call_output",
+                    "call_id": result.tool_call_id,
+                    "output": result.content,
+                }),
+                ContentTypeInner::Document(doc) => {
+                    content.push(doc.to_openai());
+                    continue;
+                }
+                ContentTypeInner::Text(text) => {
+                    content.push(serde_json::to_value(text).map_err(S::Error::custom)?);
+                    continue;
+                }
+                ContentTypeInner::Thinking(_) => continue,
+            };
+            if !content.is_empty() {
+                seq.serialize_element(&InputMessage {
+                    role: &message.role,
+                    content: std::mem::take(&mut content),
+                })?;
+            }
+            seq.serialize_element(&item)?;
+        }
+        if !content.is_empty() {
+            seq.serialize_element(&InputMessage {
+                role: &message.role,
+                content,
+            })?;
+        }
+    }
+    seq.end()
 }
 
 #[pymethods]
 impl OpenAIRequest {
     #[new]
-    #[pyo3(signature = (model,messages,prompt=None,endpoint=None,max_output_tokens=None))]
+    #[pyo3(signature = (model,messages,prompt=None,endpoint=None,max_output_tokens=None,tools=None,tool_choice=None,generation_config=None,reasoning_effort=None,reasoning_summary=None))]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         model: &str,
         messages: Vec<Message>,
         prompt: Option<&str>,
         endpoint: Option<&str>,
         max_output_tokens: Option<u32>,
-    ) -> Self {
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<&str>,
+        generation_config: Option<GenerationConfig>,
+        reasoning_effort: Option<&str>,
+        reasoning_summary: Option<&str>,
+    ) -> PyResult<Self> {
         let modified_messages = messages
             .into_iter()
             .map(|mut msg| {
@@ -59,27 +163,41 @@ impl OpenAIRequest {
             })
             .collect::<Vec<Message>>();
 
-        Self {
-            model: SupportedModels::from_str(model).unwrap_or_else(|e| {
-                eprintln!(
-                    "{}, using default model: {}",
-                    e,
-                    SupportedModels::default().to_str()
-                );
-                SupportedModels::default()
-            }),
+        let model = SupportedModels::from_str(model)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if let Some(config) = &generation_config {
+            config
+                .to_openai(&model)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+        Ok(Self {
             input: modified_messages,
             instructions: prompt.map(|s| s.to_string()),
             endpoint: endpoint.map(|s| s.to_string()),
-            reasoning: {
-                if model.contains("gpt-5") {
-                    Some(OpenAIReasoning::default())
-                } else {
-                    None
+            // unknown models (e.g. deployments on Azure) take the reasoning options as given
+            reasoning: match (
+                model.info().map(|info| info.reasoning),
+                reasoning_effort.is_some() || reasoning_summary.is_some(),
+            ) {
+                (Some(true), _) | (None, true) => {
+                    Some(OpenAIReasoning::new(reasoning_effort, reasoning_summary)?)
+                }
+                (Some(false), true) => {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                        "{} is not a reasoning model",
+                        model.to_str()
+                    )));
                 }
+                _ => None,
             },
+            model,
             max_output_tokens,
-        }
+            tools: tools.unwrap_or_default(),
+            tool_choice: tool_choice.map(|s| s.to_string()),
+            text: None,
+            stream: None,
+            generation_config,
+        })
     }
 
     fn __repr__(&self) -> PyResult<String> {
@@ -87,8 +205,8 @@ impl OpenAIRequest {
     }
 
     pub fn add_response(&mut self, response: LLMResponse) -> PyResult<()> {
-        let resp = match response.output.is_some() {
-            true => response.output.unwrap(),
+        let resp = match response.output.is_some() || !response.tool_calls.is_empty() {
+            true => response.output.unwrap_or_default(),
             false => {
                 return Err(pyo3::exceptions::PyValueError::new_err(
                     "No choices in response",
@@ -96,20 +214,33 @@ impl OpenAIRequest {
             }
         };
 
-        if resp.len() != 1 {
+        if resp.len() > 1 {
             return Err(pyo3::exceptions::PyValueError::new_err(
                 "More than one output in response",
             ));
         }
 
-        self.input.push(Message {
-            role: resp[0].role.clone(), // TODO! Risky
-            content: vec![Content {
+        let mut content: Vec<Content> = resp
+            .iter()
+            .map(|choice| Content {
                 ctx: ContentTypeInner::Text(TextContent {
                     content_type: "output_text".to_string(),
-                    text: resp[0].content[0].text.clone(), // TODO! Risky
+                    text: choice.content[0].text.clone(), // TODO! Risky
                 }),
-            }],
+                cache_control: None,
+            })
+            .collect();
+        // the function calls must be kept for the following function_call_output items
+        content.extend(response.tool_calls.into_iter().map(|call| Content {
+            ctx: ContentTypeInner::ToolCall(call),
+            cache_control: None,
+        }));
+
+        self.input.push(Message {
+            role: resp
+                .first()
+                .map_or("assistant".to_string(), |choice| choice.role.clone()),
+            content,
         });
 
         Ok(())
@@ -123,6 +254,7 @@ impl OpenAIRequest {
                     content_type: "output_text".to_string(),
                     text: response,
                 }),
+                cache_control: None,
             }],
         });
         Ok(())
@@ -141,59 +273,6 @@ impl OpenAIRequest {
     fn model(&self) -> PyResult<String> {
         Ok(self.model.to_str().to_string())
     }
-
-    fn msg_to_list_hashmap(&self, py: Python) -> PyResult<Py<PyAny>> {
-        use crate::message::ContentTypeInner;
-        use serde_json::{Value, json};
-        use std::collections::HashMap;
-
-        let mut result: Vec<HashMap<String, Value>> = Vec::new();
-
-        for message in &self.input {
-            for content in &message.content {
-                match &content.ctx {
-                    ContentTypeInner::Text(text_content) => {
-                        if text_content.content_type == "input_text"
-                            || text_content.content_type == "output_text"
-                        {
-                            let mut map: HashMap<String, Value> = HashMap::new();
-                            map.insert("role".to_string(), Value::String(message.role.clone()));
-                            map.insert(
-                                "content".to_string(),
-                                json!([{
-                                    "type": text_content.content_type,
-                                    "text": text_content.text
-                                }]),
-                            );
-                            result.push(map);
-                        }
-                    }
-                    ContentTypeInner::Document(doc_content) => {
-                        if doc_content.content_type == "input_file" {
-                            let mut map: HashMap<String, Value> = HashMap::new();
-                            map.insert("role".to_string(), Value::String(message.role.clone()));
-                            map.insert(
-                                "content".to_string(),
-                                json!([{
-                                    "type": doc_content.content_type,
-                                    "file_data": doc_content.file_data.clone(),
-                                    "filename": doc_content.filename.clone()
-                                }]),
-                            );
-                            result.push(map);
-                        } else {
-                            return Err(pyo3::exceptions::PyValueError::new_err(format!(
-                                "Unsupported content type: {}",
-                                doc_content.content_type
-                            )));
-                        }
-                    }
-                }
-            }
-        }
-
-        pythonize(py, &result)?.into_py_any(py)
-    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -255,12 +334,12 @@ where
             let mut responses = Vec::new();
 
             while let Some(value) = seq.next_element::<serde_json::Value>()? {
-                if let Some(type_field) = value.get("type") {
-                    if type_field == "message" {
-                        let response: ResponseChoiceOpenAI =
-                            serde_json::from_value(value).map_err(de::Error::custom)?;
-                        responses.push(response);
-                    }
+                if let Some(type_field) = value.get("type")
+                    && type_field == "message"
+                {
+                    let response: ResponseChoiceOpenAI =
+                        serde_json::from_value(value).map_err(de::Error::custom)?;
+                    responses.push(response);
                 }
             }
 
@@ -281,3 +360,286 @@ pub struct OpenAIResError {
     pub code: Option<String>,
     pub message: Option<String>,
 }
+
+#[test]
+fn test_serialize_input_with_tool_calls() {
+    use crate::message::ToolResult;
+    use crate::tools::ToolCall;
+
+    let mut request_body = OpenAIRequest::new(
+        "gpt-4.1-nano",
+        vec![Message {
+            role: "user".to_string(),
+            content: vec![Content {
+                ctx: ContentTypeInner::Text(TextContent::new("Weather in Berlin?").unwrap()),
+                cache_control: None,
+            }],
+        }],
+        None,
+        None,
+        None,
+        None,
+        Some("required"),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let tool_call = ToolCall {
+        id: "call_1".to_string(),
+        name: "get_weather".to_string(),
+        arguments: json!({"city": "Berlin"}),
+    };
+    request_body.input.push(Message {
+        role: "assistant".to_string(),
+        content: vec![Content {
+            ctx: ContentTypeInner::ToolCall(tool_call),
+            cache_control: None,
+        }],
+    });
+    request_body.add_message(Message {
+        role: "user".to_string(),
+        content: vec![Content {
+            ctx: ContentTypeInner::ToolResult(ToolResult::new("call_1", "Sunny", None, false)),
+            cache_control: None,
+        }],
+    });
+
+    let value = serde_json::to_value(&request_body).unwrap();
+    let input = value["input"].as_array().unwrap();
+    assert_eq!(input.len(), 3);
+    assert_eq!(input[0]["content"][0]["type"], "input_text");
+    assert_eq!(input[1]["type"], "function_call");
+    assert_eq!(input[1]["arguments"], "{\"city\":\"Berlin\"}");
+    assert_eq!(input[2]["type"], "function_call_output");
+    assert_eq!(input[2]["output"], "Sunny");
+    assert_eq!(value["tool_choice"], "required");
+}
+
+/// A request to `/v1/chat/completions` of any OpenAI compatible server (vLLM, llama.cpp, LM Studio, ...)
+#[derive(Serialize, Clone)]
+#[pyclass(dict, subclass)]
+pub struct OpenAICompatRequest {
+    #[pyo3(get, set)]
+    pub(crate) base_url: String,
+    #[pyo3(get, set)]
+    pub(crate) model: String, // passed as is, the server decides which names it knows
+    #[pyo3(get, set)]
+    pub(crate) system: Option<String>,
+    #[pyo3(get, set)]
+    pub(crate) messages: Vec<Message>,
+    #[serde(skip)]
+    #[pyo3(set)]
+    pub(crate) api_key: Option<String>, // write-only, it must not end up in logs
+    #[pyo3(get, set)]
+    pub(crate) auth_header: String,
+    #[pyo3(get, set)]
+    pub(crate) max_tokens: Option<u32>,
+    #[pyo3(get, set)]
+    pub(crate) tools: Vec<Tool>,
+    #[pyo3(get, set)]
+    pub(crate) tool_choice: Option<String>,
+    #[pyo3(get, set)]
+    pub(crate) format: Option<Json>, // structured output
+    #[pyo3(get, set)]
+    pub(crate) generation_config: Option<GenerationConfig>,
+}
+
+#[pymethods]
+impl OpenAICompatRequest {
+    #[new]
+    #[pyo3(signature = (base_url, model, messages, prompt=None, api_key=None, auth_header="Authorization", max_tokens=None, tools=None, tool_choice=None, generation_config=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        base_url: &str,
+        model: &str,
+        messages: Vec<Message>,
+        prompt: Option<&str>,
+        api_key: Option<&str>,
+        auth_header: &str,
+        max_tokens: Option<u32>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<&str>,
+        generation_config: Option<GenerationConfig>,
+    ) -> PyResult<Self> {
+        if let Some(config) = &generation_config {
+            config
+                .to_chat_completions()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+        Ok(Self {
+            base_url: base_url.to_string(),
+            model: model.to_string(),
+            system: prompt.map(|s| s.to_string()),
+            messages,
+            api_key: api_key.map(|s| s.to_string()),
+            auth_header: auth_header.to_string(),
+            max_tokens,
+            tools: tools.unwrap_or_default(),
+            tool_choice: tool_choice.map(|s| s.to_string()),
+            format: None,
+            generation_config,
+        })
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        // the key is not shown, reprs end up in logs
+        Ok(format!(
+            "OpenAICompatRequest<base_url={},model={},api_key={},auth_header={},messages={:?}>",
+            self.base_url,
+            self.model,
+            if self.api_key.is_some() {
+                "***"
+            } else {
+                "None"
+            },
+            self.auth_header,
+            self.messages
+        ))
+    }
+
+    pub fn add_response(&mut self, response: LLMResponse) {
+        let mut content: Vec<Content> = response
+            .content
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|block| !block.text.is_empty())
+            .map(|block| Content {
+                ctx: ContentTypeInner::Text(TextContent {
+                    content_type: "text".to_string(),
+                    text: block.text,
+                }),
+                cache_control: None,
+            })
+            .collect();
+        content.extend(response.tool_calls.into_iter().map(|call| Content {
+            ctx: ContentTypeInner::ToolCall(call),
+            cache_control: None,
+        }));
+
+        self.messages.push(Message {
+            role: "assistant".to_string(),
+            content,
+        });
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+}
+
+impl std::fmt::Debug for OpenAICompatRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAICompatRequest")
+            .field("base_url", &self.base_url)
+            .field("model", &self.model)
+            .field("system", &self.system)
+            .field("messages", &self.messages)
+            .field("api_key", &self.api_key.as_ref().map(|_| "***"))
+            .field("auth_header", &self.auth_header)
+            .field("max_tokens", &self.max_tokens)
+            .field("tools", &self.tools)
+            .field("tool_choice", &self.tool_choice)
+            .field("format", &self.format)
+            .field("generation_config", &self.generation_config)
+            .finish()
+    }
+}
+
+impl OpenAICompatRequest {
+    /// `base_url` is the root of the API (e.g. `http://localhost:8000/v1`), a full
+    /// `/chat/completions` URL is accepted as well
+    pub(crate) fn url(&self) -> String {
+        let base_url = self.base_url.trim_end_matches('/');
+        match base_url.ends_with("/chat/completions") {
+            true => base_url.to_string(),
+            false => format!("{}/chat/completions", base_url),
+        }
+    }
+
+    /// The value of `auth_header`, a bearer token for `Authorization` and the plain key otherwise
+    ///
+    /// The key of the request, else the one of the current `GoldenAIClient`
+    pub(crate) fn auth_value(&self) -> Option<String> {
+        let api_key = self.api_key.clone().or_else(|| {
+            crate::client::settings("openai_compat").and_then(|settings| settings.api_key)
+        })?;
+        match self.auth_header.eq_ignore_ascii_case("authorization") {
+            true => Some(format!("Bearer {}", api_key)),
+            false => Some(api_key.clone()),
+        }
+    }
+
+    pub fn to_chat_completions(&self, stream: bool) -> anyhow::Result<ChatCompletionsRequest> {
+        let mut body = ChatCompletionsRequest::new(
+            &self.model,
+            self.system.as_deref(),
+            &self.messages,
+            self.max_tokens,
+            &self.tools,
+            self.tool_choice.as_deref(),
+        );
+        body.response_format = self.format.as_ref().map(|format| format.0.clone());
+        body.stream = stream;
+        if stream {
+            // without it, OpenAI and vLLM send no usage in a stream
+            body.stream_options = Some(json!({"include_usage": true}));
+        }
+        if let Some(config) = &self.generation_config {
+            body.params = config.to_chat_completions()?;
+        }
+        Ok(body)
+    }
+}
+
+#[test]
+fn test_reasoning_openai() {
+    let request_body = OpenAIRequest::new(
+        "gpt-5-nano",
+        vec![],
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some("high"),
+        Some("auto"),
+    )
+    .unwrap();
+    let value = serde_json::to_value(&request_body).unwrap();
+    assert_eq!(
+        value["reasoning"],
+        json!({"effort": "high", "summary": "auto"})
+    );
+    assert!(
+        OpenAIRequest::new(
+            "gpt-4.1-nano",
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some("high"),
+            None
+        )
+        .is_err()
+    );
+
+    let response = LLMResponse::from_json(json!({
+        "id": "resp_1",
+        "object": "response",
+        "model": "gpt-5-nano",
+        "output": [
+            {"type": "reasoning", "id": "rs_1", "summary": [{"type": "summary_text", "text": "Thinking about colors"}]},
+            {"type": "message", "id": "msg_1", "status": "completed", "role": "assistant", "content": [{"type": "output_text", "text": "White", "annotations": []}]},
+        ],
+        "usage": {"input_tokens": 10, "output_tokens": 50, "output_tokens_details": {"reasoning_tokens": 45}},
+    }))
+    .unwrap();
+    assert_eq!(response.to_string(), "White");
+    assert_eq!(response.reasoning_summary, vec!["Thinking about colors"]);
+    assert_eq!(response.usage.reasoning_tokens, 45);
+}
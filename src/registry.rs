@@ -0,0 +1,203 @@
+use anyhow::{Result, anyhow};
+use pyo3::exceptions::{PyException, PyUserWarning, PyValueError};
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+static REGISTRY: OnceLock<RwLock<Vec<ModelInfo>>> = OnceLock::new();
+
+/// What is known about a model: its names, provider, limits, capabilities and prices
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[pyclass(dict, get_all, frozen)]
+pub struct ModelInfo {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>, // e.g. dated snapshots, resolved to `name`
+    pub provider: String, // "openai", "anthropic", "ollama", "gemini" or "mistral"
+    pub context_window: Option<u32>,
+    pub max_output_tokens: Option<u32>,
+    #[serde(default)]
+    pub vision: bool,
+    #[serde(default)]
+    pub pdf: bool,
+    #[serde(default)]
+    pub tools: bool,
+    #[serde(default)]
+    pub reasoning: bool,
+    pub input_price: Option<f64>, // USD per million tokens
+    pub output_price: Option<f64>,
+    pub cache_read_price: Option<f64>, // prompt caching, input_price if not set
+    pub cache_write_price: Option<f64>, // Anthropic, the 5 minute cache
+    #[serde(default)]
+    pub cache_write_1h_price: Option<f64>, // Anthropic, the 1 hour cache
+}
+
+#[pymethods]
+impl ModelInfo {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "ModelInfo<name={:?}, provider={:?}>",
+            self.name, self.provider
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelTable {
+    models: Vec<ModelInfo>,
+}
+
+impl ModelInfo {
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+/// The built-in table, merged with the file in `GOLDENAI_MODELS` if it is set
+fn registry() -> &'static RwLock<Vec<ModelInfo>> {
+    REGISTRY.get_or_init(|| {
+        let path = env::var("GOLDENAI_MODELS")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let (models, error) = load(path.as_deref().map(Path::new));
+        if let Some(e) = error {
+            warn(&format!("Ignoring GOLDENAI_MODELS: {}", e));
+        }
+        RwLock::new(models)
+    })
+}
+
+/// Read the registry on import of the module, so that a warning about `GOLDENAI_MODELS` points at
+/// the import rather than at the first call that looks up a model
+pub(crate) fn init() {
+    registry();
+}
+
+/// The built-in table merged with the file at `path`, and the error of reading that file
+fn load(path: Option<&Path>) -> (Vec<ModelInfo>, Option<anyhow::Error>) {
+    let mut models = parse_models(include_str!("models.toml"), true)
+        .expect("The built-in model table is invalid")
+        .models;
+    let Some(path) = path else {
+        return (models, None);
+    };
+    match read_models(path) {
+        Ok(overrides) => {
+            merge(&mut models, overrides);
+            (models, None)
+        }
+        Err(e) => (models, Some(e)),
+    }
+}
+
+/// A `UserWarning` of Python, which is printed unless it is filtered, or raised as error where
+/// warnings are errors (and then reported as unraisable, as no call can fail with it)
+fn warn(message: &str) {
+    crate::runtime::attach_unless_shutdown(|py| {
+        let result = CString::new(message)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+            .and_then(|message| PyErr::warn(py, &py.get_type::<PyUserWarning>(), &message, 1));
+        if let Err(e) = result {
+            e.write_unraisable(py, None);
+        }
+    });
+}
+
+fn parse_models(content: &str, is_toml: bool) -> Result<ModelTable> {
+    Ok(match is_toml {
+        true => toml::from_str(content)?,
+        false => serde_json::from_str(content)?,
+    })
+}
+
+/// A TOML file (`.toml`) or JSON file (anything else) with a `models` list
+fn read_models(path: &Path) -> Result<Vec<ModelInfo>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+    parse_models(&content, is_toml)
+        .map(|table| table.models)
+        .map_err(|e| anyhow!("Invalid model table {}: {}", path.display(), e))
+}
+
+/// Entries of the same name are replaced, new ones are added
+fn merge(models: &mut Vec<ModelInfo>, overrides: Vec<ModelInfo>) {
+    for model in overrides {
+        match models.iter_mut().find(|known| known.name == model.name) {
+            Some(known) => *known = model,
+            None => models.push(model),
+        }
+    }
+}
+
+/// The entry of `name` or one of its aliases
+pub(crate) fn lookup(name: &str) -> Option<ModelInfo> {
+    registry()
+        .read()
+        .unwrap()
+        .iter()
+        .find(|model| model.matches(name))
+        .cloned()
+}
+
+/// Every known model, optionally only those of one provider
+#[pyfunction]
+#[pyo3(signature = (provider=None))]
+pub fn list_models(provider: Option<&str>) -> Vec<ModelInfo> {
+    registry()
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|model| provider.is_none_or(|provider| model.provider == provider))
+        .cloned()
+        .collect()
+}
+
+/// The entry of a model name or alias, None for unknown models
+#[pyfunction]
+pub fn model_info(name: &str) -> Option<ModelInfo> {
+    lookup(name)
+}
+
+/// Add the models of a TOML or JSON file, replacing the entries of the same name
+#[pyfunction]
+pub fn load_models(path: &str) -> PyResult<()> {
+    let models = read_models(Path::new(path)).map_err(|e| PyException::new_err(e.to_string()))?;
+    merge(&mut registry().write().unwrap(), models);
+    Ok(())
+}
+
+#[test]
+fn test_registry() {
+    let model = lookup("gpt-5-2025-08-07").unwrap();
+    assert_eq!(model.name, "gpt-5");
+    assert!(model.reasoning);
+    assert!(lookup("llama3.2:3b").is_none());
+
+    let mut models = parse_models(include_str!("models.toml"), true)
+        .unwrap()
+        .models;
+    let overrides = parse_models(
+        r#"{"models": [
+            {"name": "gpt-5", "provider": "openai", "input_price": 1.0, "output_price": 8.0},
+            {"name": "llama3.2:3b", "aliases": ["llama3.2"], "provider": "ollama", "tools": true}
+        ]}"#,
+        false,
+    )
+    .unwrap()
+    .models;
+    let count = models.len();
+    merge(&mut models, overrides);
+    assert_eq!(models.len(), count + 1);
+    let gpt5 = models.iter().find(|model| model.matches("gpt-5")).unwrap();
+    assert_eq!(gpt5.input_price, Some(1.0));
+    assert!(models.iter().any(|model| model.matches("llama3.2")));
+
+    // a file that cannot be read leaves the built-in table as it is
+    let (models, error) = load(Some(Path::new("does-not-exist.toml")));
+    assert_eq!(models.len(), count);
+    assert!(error.unwrap().to_string().contains("does-not-exist.toml"));
+}
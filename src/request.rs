@@ -0,0 +1,193 @@
+use anyhow::Result;
+use pyo3::prelude::*;
+use reqwest::Response;
+
+use crate::SupportedModels;
+use crate::anthropic::anthropic::{
+    count_tokens_anthropic, get_response_anthropic, request_anthropic, request_anthropic_stream,
+};
+use crate::anthropic::structs::AnthropicRequest;
+use crate::error::{InvalidRequestError, new_err};
+use crate::gemini::gemini::{
+    count_tokens_gemini, get_response_gemini, request_gemini, request_gemini_stream,
+};
+use crate::gemini::structs::GeminiRequest;
+use crate::message::Message;
+use crate::mistral::mistral::{
+    count_tokens_mistral, get_response_mistral, request_mistral, request_mistral_stream,
+};
+use crate::mistral::structs::MistralRequest;
+use crate::ollama::ollama::{get_response_ollama, request_ollama, request_ollama_stream};
+use crate::ollama::structs::OllamaRequest;
+use crate::openai::azure::{self, AzureSettings};
+use crate::openai::openai::{
+    count_tokens_openai_compat, get_count_tokens_openai, get_response_openai,
+    get_response_openai_compat, request_openai, request_openai_compat,
+    request_openai_compat_stream, request_openai_stream,
+};
+use crate::openai::structs::{OpenAICompatRequest, OpenAIRequest};
+use crate::response::LLMResponse;
+use crate::stream::StreamFormat;
+
+/// The request of any provider, for the loops that drive a conversation on their own
+#[derive(Clone)]
+pub(crate) enum ProviderRequest {
+    Anthropic(AnthropicRequest),
+    OpenAI(OpenAIRequest),
+    Ollama(OllamaRequest),
+    Gemini(GeminiRequest),
+    Mistral(MistralRequest),
+    OpenAICompat(OpenAICompatRequest),
+}
+
+impl ProviderRequest {
+    pub(crate) fn extract(request_body: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(anthropic_req) = request_body.extract::<AnthropicRequest>() {
+            Ok(Self::Anthropic(anthropic_req))
+        } else if let Ok(openai_req) = request_body.extract::<OpenAIRequest>() {
+            Ok(Self::OpenAI(openai_req))
+        } else if let Ok(ollama_req) = request_body.extract::<OllamaRequest>() {
+            Ok(Self::Ollama(ollama_req))
+        } else if let Ok(gemini_req) = request_body.extract::<GeminiRequest>() {
+            Ok(Self::Gemini(gemini_req))
+        } else if let Ok(mistral_req) = request_body.extract::<MistralRequest>() {
+            Ok(Self::Mistral(mistral_req))
+        } else if let Ok(compat_req) = request_body.extract::<OpenAICompatRequest>() {
+            Ok(Self::OpenAICompat(compat_req))
+        } else {
+            Err(new_err::<InvalidRequestError>("Invalid request body"))
+        }
+    }
+
+    /// Write the conversation back into the request object of the caller
+    pub(crate) fn store(self, request_body: &Bound<'_, PyAny>) -> PyResult<()> {
+        match self {
+            Self::Anthropic(req) => {
+                *request_body.downcast::<AnthropicRequest>()?.borrow_mut() = req
+            }
+            Self::OpenAI(req) => *request_body.downcast::<OpenAIRequest>()?.borrow_mut() = req,
+            Self::Ollama(req) => *request_body.downcast::<OllamaRequest>()?.borrow_mut() = req,
+            Self::Gemini(req) => *request_body.downcast::<GeminiRequest>()?.borrow_mut() = req,
+            Self::Mistral(req) => *request_body.downcast::<MistralRequest>()?.borrow_mut() = req,
+            Self::OpenAICompat(req) => {
+                *request_body.downcast::<OpenAICompatRequest>()?.borrow_mut() = req
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn model(&self) -> SupportedModels {
+        match self {
+            Self::Anthropic(req) => req.model.clone(),
+            Self::OpenAI(req) => req.model.clone(),
+            Self::Ollama(req) => req.model.clone(),
+            Self::Gemini(req) => req.model.clone(),
+            Self::Mistral(req) => req.model.clone(),
+            Self::OpenAICompat(req) => SupportedModels::Custom(req.model.clone()),
+        }
+    }
+
+    /// `chat` only applies to Ollama
+    pub(crate) fn send(&self, chat: bool) -> Result<LLMResponse> {
+        match self {
+            Self::Anthropic(req) => get_response_anthropic(req.clone()),
+            Self::OpenAI(req) => get_response_openai(req.clone()),
+            Self::Ollama(req) => get_response_ollama(req.clone(), chat),
+            Self::Gemini(req) => get_response_gemini(req.clone()),
+            Self::Mistral(req) => get_response_mistral(req.clone()),
+            Self::OpenAICompat(req) => get_response_openai_compat(req.clone()),
+        }
+    }
+
+    /// The Azure settings of an OpenAI request, resolved where the GIL is held: before the request
+    /// future is started, which must not call Python on a worker of the runtime
+    fn azure(&self) -> Result<Option<AzureSettings>> {
+        match self {
+            Self::OpenAI(req) => azure::resolve(req.endpoint.as_deref()),
+            _ => Ok(None),
+        }
+    }
+
+    /// The response to the request, `chat` only applies to Ollama
+    pub(crate) fn request(
+        self,
+        chat: bool,
+    ) -> impl Future<Output = Result<LLMResponse>> + Send + 'static {
+        let azure = self.azure();
+        async move {
+            match self {
+                Self::Anthropic(req) => request_anthropic(req).await,
+                Self::OpenAI(req) => request_openai(req, azure?).await,
+                Self::Ollama(req) => request_ollama(req, chat).await,
+                Self::Gemini(req) => request_gemini(req).await,
+                Self::Mistral(req) => request_mistral(req).await,
+                Self::OpenAICompat(req) => request_openai_compat(req).await,
+            }
+        }
+    }
+
+    /// The raw streamed response, to be parsed in `stream_format(chat)`
+    pub(crate) fn request_stream(
+        self,
+        chat: bool,
+    ) -> impl Future<Output = Result<Response>> + Send + 'static {
+        let azure = self.azure();
+        async move {
+            match self {
+                Self::Anthropic(req) => request_anthropic_stream(req).await,
+                Self::OpenAI(req) => request_openai_stream(req, azure?).await,
+                Self::Ollama(req) => request_ollama_stream(req, chat).await,
+                Self::Gemini(req) => request_gemini_stream(req).await,
+                Self::Mistral(req) => request_mistral_stream(req).await,
+                Self::OpenAICompat(req) => request_openai_compat_stream(req).await,
+            }
+        }
+    }
+
+    pub(crate) fn stream_format(&self, chat: bool) -> StreamFormat {
+        match self {
+            Self::Anthropic(_) => StreamFormat::Anthropic,
+            Self::OpenAI(_) => StreamFormat::OpenAI,
+            Self::Ollama(_) if chat => StreamFormat::OllamaChat,
+            Self::Ollama(_) => StreamFormat::Ollama,
+            Self::Gemini(_) => StreamFormat::Gemini,
+            Self::Mistral(_) | Self::OpenAICompat(_) => StreamFormat::ChatCompletions,
+        }
+    }
+
+    pub(crate) async fn count_tokens(self) -> Result<u32> {
+        match self {
+            Self::Anthropic(req) => count_tokens_anthropic(req).await,
+            // counted locally with the tokenizer of the model, documents are not counted
+            Self::OpenAI(req) => get_count_tokens_openai(req),
+            Self::Ollama(_) => Ok(0), // TODO! Ollama is not necessary to count tokens for now
+            Self::Gemini(req) => count_tokens_gemini(req).await,
+            // counted locally where the tokenizer is known, otherwise not supported
+            Self::Mistral(req) => count_tokens_mistral(req),
+            Self::OpenAICompat(req) => count_tokens_openai_compat(req),
+        }
+    }
+
+    pub(crate) fn add_response(&mut self, response: LLMResponse) -> PyResult<()> {
+        match self {
+            Self::Anthropic(req) => req.add_response(response),
+            Self::OpenAI(req) => req.add_response(response)?,
+            Self::Ollama(req) => req.add_response(response),
+            Self::Gemini(req) => req.add_response(response),
+            Self::Mistral(req) => req.add_response(response),
+            Self::OpenAICompat(req) => req.add_response(response),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_message(&mut self, message: Message) {
+        match self {
+            Self::Anthropic(req) => req.add_message(message),
+            Self::OpenAI(req) => req.add_message(message),
+            Self::Ollama(req) => req.add_message(message),
+            Self::Gemini(req) => req.add_message(message),
+            Self::Mistral(req) => req.add_message(message),
+            Self::OpenAICompat(req) => req.add_message(message),
+        }
+    }
+}
@@ -1,39 +1,161 @@
 use pyo3::exceptions::PyTypeError;
-use pyo3::{PyResult, pyclass, pymethods};
+use pyo3::{PyErr, PyResult, pyclass, pymethods};
 use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 
 use crate::SupportedModels;
-use crate::anthropic::structs::ResponseAnthropic;
+use crate::anthropic::structs::{ResponseAnthropic, deserialize_text_only};
+use crate::message::ThinkingContent;
 use crate::openai::structs::{
     OpenAIReasoning, OpenAIResError, ResponseChoiceOpenAI, deserialize_message_only,
 };
+use crate::schema::Json;
+use crate::tools::ToolCall;
 
-#[derive(Deserialize, Debug, Clone)]
+/// The tokens of a response, `input_tokens` includes the cached tokens for every provider
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(from = "RawUsage")]
 #[pyclass(dict, get_all, set_all)]
 pub struct Usage {
-    #[serde(alias = "prompt_tokens")]
     pub input_tokens: u32,
-    #[serde(alias = "completion_tokens")]
     pub output_tokens: u32,
+    pub reasoning_tokens: u32, // part of output_tokens, billed as output
+    pub cache_creation_input_tokens: u32, // part of input_tokens, Anthropic only
+    pub cache_read_input_tokens: u32, // part of input_tokens
+    pub details: BTreeMap<String, u32>, // provider-specific, e.g. "input_audio_tokens" or "output_image_tokens"
+    pub durations: BTreeMap<String, f64>, // in seconds, Ollama only: "total", "load", "prompt_eval" and "eval"
+}
+
+/// The usage of the Responses API, Chat Completions or Anthropic
+#[derive(Deserialize)]
+struct RawUsage {
+    #[serde(alias = "prompt_tokens")]
+    input_tokens: u32,
+    #[serde(alias = "completion_tokens")]
+    output_tokens: u32,
+    #[serde(default, alias = "completion_tokens_details")]
+    output_tokens_details: Option<Map<String, Value>>, // OpenAI
+    #[serde(default, alias = "prompt_tokens_details")]
+    input_tokens_details: Option<Map<String, Value>>, // OpenAI
+    #[serde(default)]
+    cache_creation_input_tokens: u32, // Anthropic, not part of its input_tokens
+    #[serde(default)]
+    cache_read_input_tokens: u32, // Anthropic, not part of its input_tokens
+    #[serde(default)]
+    cache_creation: Option<Map<String, Value>>, // Anthropic, by ttl of the cache
+    #[serde(default)]
+    server_tool_use: Option<Map<String, Value>>, // Anthropic, e.g. web_search_requests
+}
+
+/// The counts of a details object, the ones with a field of their own in Usage are left out
+fn count_details(
+    details: &mut BTreeMap<String, u32>,
+    prefix: &str,
+    object: Option<&Map<String, Value>>,
+) {
+    for (name, count) in object.into_iter().flatten() {
+        if let Some(count) = count.as_u64()
+            && !["cached_tokens", "reasoning_tokens"].contains(&name.as_str())
+        {
+            details.insert(format!("{}{}", prefix, name), count as u32);
+        }
+    }
+}
+
+impl From<RawUsage> for Usage {
+    fn from(usage: RawUsage) -> Self {
+        let count = |object: &Option<Map<String, Value>>, name: &str| {
+            object
+                .as_ref()
+                .and_then(|object| object.get(name))
+                .and_then(Value::as_u64)
+                .unwrap_or(0) as u32
+        };
+        let mut details = BTreeMap::new();
+        count_details(&mut details, "input_", usage.input_tokens_details.as_ref());
+        count_details(
+            &mut details,
+            "output_",
+            usage.output_tokens_details.as_ref(),
+        );
+        count_details(&mut details, "", usage.cache_creation.as_ref());
+        count_details(&mut details, "", usage.server_tool_use.as_ref());
+
+        Self {
+            input_tokens: usage.input_tokens
+                + usage.cache_creation_input_tokens
+                + usage.cache_read_input_tokens,
+            output_tokens: usage.output_tokens,
+            reasoning_tokens: count(&usage.output_tokens_details, "reasoning_tokens"),
+            cache_creation_input_tokens: usage.cache_creation_input_tokens,
+            cache_read_input_tokens: usage.cache_read_input_tokens
+                + count(&usage.input_tokens_details, "cached_tokens"),
+            details,
+            durations: BTreeMap::new(),
+        }
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        for (name, count) in other.details {
+            *self.details.entry(name).or_default() += count;
+        }
+        for (name, duration) in other.durations {
+            *self.durations.entry(name).or_default() += duration;
+        }
+    }
+}
+
+impl std::ops::Add for Usage {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
 }
 
 #[pymethods]
 impl Usage {
-    fn __repr__(&self) -> PyResult<String> {
-        Ok(format!(
-            "Usage<input_tokens={:?},output_tokens={:?}>",
-            self.input_tokens, self.output_tokens
-        ))
+    #[getter]
+    pub fn total_tokens(&self) -> u32 {
+        self.input_tokens + self.output_tokens
     }
-}
 
-impl Default for Usage {
-    fn default() -> Self {
-        Usage {
-            input_tokens: 0,
-            output_tokens: 0,
+    fn __add__(&self, other: Usage) -> Usage {
+        self.clone() + other
+    }
+
+    // for sum(), which starts with 0
+    fn __radd__(&self, other: u32) -> PyResult<Usage> {
+        match other {
+            0 => Ok(self.clone()),
+            _ => Err(PyTypeError::new_err(
+                "Only a Usage or 0 can be added to a Usage",
+            )),
         }
     }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "Usage<input_tokens={:?},output_tokens={:?},total_tokens={:?},reasoning_tokens={:?},cache_creation_input_tokens={:?},cache_read_input_tokens={:?},details={:?},durations={:?}>",
+            self.input_tokens,
+            self.output_tokens,
+            self.total_tokens(),
+            self.reasoning_tokens,
+            self.cache_creation_input_tokens,
+            self.cache_read_input_tokens,
+            self.details,
+            self.durations
+        ))
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, Default)]
@@ -45,9 +167,12 @@ pub struct LLMResponse {
     pub response_type: String,
     pub usage: Usage,
 
-    pub role: Option<String>,                    // Anthropic
+    pub role: Option<String>, // Anthropic
+    #[serde(default, deserialize_with = "deserialize_text_only")]
     pub content: Option<Vec<ResponseAnthropic>>, // Anthropic
-    pub stop_reason: Option<String>,             // Anthropic
+    pub stop_reason: Option<String>, // Anthropic
+    #[serde(skip)]
+    pub thinking: Vec<ThinkingContent>, // Anthropic, extended thinking
 
     #[serde(default, deserialize_with = "deserialize_message_only")]
     pub output: Option<Vec<ResponseChoiceOpenAI>>, // OpenAI
@@ -55,6 +180,17 @@ pub struct LLMResponse {
     pub instructions: Option<String>,       // OpenAI
     pub error: Option<OpenAIResError>,      // OpenAI
     pub status: Option<String>,             // OpenAI
+
+    pub context: Option<Vec<i64>>, // Ollama generate mode
+
+    #[serde(skip)]
+    pub reasoning_summary: Vec<String>, // OpenAI, one entry per part of the summary
+    #[serde(skip)]
+    pub tool_calls: Vec<ToolCall>, // collected from every provider by from_json
+    #[serde(skip)]
+    pub parsed: Option<Json>, // structured output, see `send(request, schema=...)`
+    #[serde(skip)]
+    pub attempts: Option<u32>, // structured output, the number of requests including re-asks
 }
 
 #[pymethods]
@@ -67,72 +203,62 @@ impl LLMResponse {
     }
 
     fn __str__(&self) -> PyResult<String> {
-        let content: String = match self.role {
-            Some(..) => {
-                // Anthropic response
-                let mut content = String::new();
-                for c in self.content.as_ref().unwrap() {
-                    content.push_str(&c.text);
-                }
-                content
-            }
-            None => {
-                // OpenAI response
-                let mut content = String::new();
-                for choice in self.output.as_ref().unwrap() {
-                    content.push_str(&choice.content[0].text); // TODO! Risky
-                }
-                content
-            }
-        };
-        Ok(format!("{}", content))
+        Ok(self.to_string())
     }
 
     pub fn cost(&self) -> PyResult<f64> {
-        let input: f64;
-        let output: f64;
-        if self.model.to_str() == "claude-3-5-haiku-latest" {
-            input = 0.8;
-            output = 4.0;
-        } else if self.model.to_str() == "gpt-4.1-nano" {
-            input = 0.1;
-            output = 0.4;
-        } else if self.model.to_str() == "gpt-4.1-mini" {
-            input = 0.4;
-            output = 1.6;
-        } else if self.model.to_str() == "gpt-4.1" {
-            input = 2.0;
-            output = 8.0;
-        } else if self.model.to_str() == "gpt-5" {
-            input = 1.25;
-            output = 10.0;
-        } else if self.model.to_str() == "gpt-5-nano" {
-            input = 0.05;
-            output = 0.4;
-        } else if self.model.to_str() == "gpt-5-mini" {
-            input = 0.25;
-            output = 2.0;
-        } else {
-            return Err(PyTypeError::new_err("Unsupported model"));
-        }
+        let Some(info) = self.model.info() else {
+            return Err(self.no_pricing());
+        };
+        let (Some(input), Some(output)) = (info.input_price, info.output_price) else {
+            // the model can still be used, only its price is not known
+            return Err(self.no_pricing());
+        };
+        // without a price of their own, cached tokens are billed as any other input
+        let cache_read = info.cache_read_price.unwrap_or(input);
+        let cache_write = info.cache_write_price.unwrap_or(input);
+        let cache_write_1h = info.cache_write_1h_price.unwrap_or(input);
 
-        let input_tokens = self.usage.input_tokens as f64;
+        let cache_read_tokens = self.usage.cache_read_input_tokens as f64;
+        let cache_write_tokens = self.usage.cache_creation_input_tokens as f64;
+        // Anthropic reports the writes by ttl, the ones without a ttl are 5 minute writes
+        let cache_write_1h_tokens = self
+            .usage
+            .details
+            .get("ephemeral_1h_input_tokens")
+            .map_or(0.0, |tokens| *tokens as f64)
+            .min(cache_write_tokens);
+        let input_tokens = self.usage.input_tokens as f64 - cache_read_tokens - cache_write_tokens;
+        // the reasoning tokens are part of the output tokens and billed at the same price
         let output_tokens = self.usage.output_tokens as f64;
 
-        let input_cost = input * input_tokens;
+        let input_cost = input * input_tokens
+            + cache_read * cache_read_tokens
+            + cache_write * (cache_write_tokens - cache_write_1h_tokens)
+            + cache_write_1h * cache_write_1h_tokens;
         let output_cost = output * output_tokens;
 
         Ok((input_cost + output_cost) / 1_000_000.0)
     }
 }
 
+impl LLMResponse {
+    fn no_pricing(&self) -> PyErr {
+        PyTypeError::new_err(format!(
+            "No pricing known for model: {}",
+            self.model.to_str()
+        ))
+    }
+}
+
 impl std::fmt::Display for LLMResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let content: String = match self.role {
             Some(..) => {
                 // Anthropic or Ollama response
                 let mut content = String::new();
-                for c in self.content.as_ref().unwrap() {
+                // a response with only tool calls has no text
+                for c in self.content.iter().flatten() {
                     content.push_str(&c.text);
                 }
                 content
@@ -140,7 +266,7 @@ impl std::fmt::Display for LLMResponse {
             None => {
                 // OpenAI response
                 let mut content = String::new();
-                for choice in self.output.as_ref().unwrap() {
+                for choice in self.output.iter().flatten() {
                     content.push_str(&choice.content[0].text); // TODO! Risky
                 }
                 content
@@ -149,3 +275,77 @@ impl std::fmt::Display for LLMResponse {
         write!(f, "{}", content)
     }
 }
+
+impl LLMResponse {
+    /// Deserialize the response body of OpenAI or Anthropic, including the tool calls and
+    /// reasoning summaries that are mixed into `output` and `content`
+    pub(crate) fn from_json(value: Value) -> serde_json::Result<Self> {
+        let tool_calls = value["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(ToolCall::from_anthropic)
+            .chain(
+                value["output"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(ToolCall::from_openai),
+            )
+            .collect();
+
+        let thinking = value["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(ThinkingContent::from_anthropic)
+            .collect();
+        let reasoning_summary = value["output"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|item| item["type"] == "reasoning")
+            .flat_map(|item| item["summary"].as_array().into_iter().flatten())
+            .filter_map(|part| part["text"].as_str().map(|text| text.to_string()))
+            .collect();
+
+        let mut response: Self = serde_json::from_value(value)?;
+        response.tool_calls = tool_calls;
+        response.thinking = thinking;
+        response.reasoning_summary = reasoning_summary;
+        Ok(response)
+    }
+}
+
+#[test]
+fn test_usage() {
+    let chat_completions: Usage = serde_json::from_value(serde_json::json!({
+        "prompt_tokens": 100,
+        "completion_tokens": 20,
+        "total_tokens": 120,
+        "prompt_tokens_details": {"cached_tokens": 64, "audio_tokens": 10},
+        "completion_tokens_details": {"reasoning_tokens": 8, "audio_tokens": null, "accepted_prediction_tokens": 0},
+    }))
+    .unwrap();
+    assert_eq!(chat_completions.cache_read_input_tokens, 64);
+    assert_eq!(chat_completions.reasoning_tokens, 8);
+    assert_eq!(chat_completions.details["input_audio_tokens"], 10);
+    assert!(!chat_completions.details.contains_key("output_audio_tokens"));
+
+    let anthropic: Usage = serde_json::from_value(serde_json::json!({
+        "input_tokens": 5,
+        "cache_creation_input_tokens": 10,
+        "cache_read_input_tokens": 0,
+        "cache_creation": {"ephemeral_5m_input_tokens": 0, "ephemeral_1h_input_tokens": 10},
+        "output_tokens": 7,
+        "service_tier": "standard",
+    }))
+    .unwrap();
+    assert_eq!(anthropic.input_tokens, 15);
+    assert_eq!(anthropic.details["ephemeral_1h_input_tokens"], 10);
+
+    let total = chat_completions + anthropic;
+    assert_eq!(total.input_tokens, 115);
+    assert_eq!(total.total_tokens(), 142);
+    assert_eq!(total.details.len(), 4);
+}
@@ -0,0 +1,384 @@
+use anyhow::{Result, anyhow};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response};
+use serde_json::Value;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::client;
+use crate::error::{ApiError, check_response};
+
+static POLICY: RwLock<Option<RetryPolicy>> = RwLock::new(None);
+
+/// When and how often a failed request is sent again, used for every provider
+#[derive(Clone, Debug, PartialEq)]
+#[pyclass(dict, get_all, frozen)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,    // including the first request, 1 to never retry
+    pub initial_backoff: f64, // in seconds, doubled (by `multiplier`) for every further attempt
+    pub max_backoff: f64,
+    pub multiplier: f64,
+    pub jitter: f64, // the fraction of the backoff which is random, 0 to 1
+    pub retry_statuses: Vec<u16>,
+    pub retry_network_errors: bool, // connection errors and timeouts
+    pub max_retry_after: f64,       // a longer wait asked for by the provider is not waited for
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: 0.5,
+            max_backoff: 30.0,
+            multiplier: 2.0,
+            jitter: 0.5,
+            // 529 is the "overloaded" of Anthropic
+            retry_statuses: vec![408, 409, 429, 500, 502, 503, 504, 529],
+            retry_network_errors: true,
+            max_retry_after: 60.0,
+        }
+    }
+}
+
+#[pymethods]
+impl RetryPolicy {
+    #[new]
+    #[pyo3(signature = (max_attempts=3, initial_backoff=0.5, max_backoff=30.0, multiplier=2.0, jitter=0.5, retry_statuses=None, retry_network_errors=true, max_retry_after=60.0))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        max_attempts: u32,
+        initial_backoff: f64,
+        max_backoff: f64,
+        multiplier: f64,
+        jitter: f64,
+        retry_statuses: Option<Vec<u16>>,
+        retry_network_errors: bool,
+        max_retry_after: f64,
+    ) -> PyResult<Self> {
+        if max_attempts == 0 {
+            return Err(PyValueError::new_err("max_attempts must be at least 1"));
+        }
+        if initial_backoff < 0.0 || max_backoff < 0.0 || max_retry_after < 0.0 {
+            return Err(PyValueError::new_err(
+                "initial_backoff, max_backoff and max_retry_after must not be negative",
+            ));
+        }
+        if multiplier < 1.0 {
+            return Err(PyValueError::new_err("multiplier must be at least 1"));
+        }
+        if !(0.0..=1.0).contains(&jitter) {
+            return Err(PyValueError::new_err("jitter must be between 0 and 1"));
+        }
+        Ok(Self {
+            max_attempts,
+            initial_backoff,
+            max_backoff,
+            multiplier,
+            jitter,
+            retry_statuses: retry_statuses.unwrap_or(Self::default().retry_statuses),
+            retry_network_errors,
+            max_retry_after,
+        })
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "RetryPolicy<max_attempts={},initial_backoff={},max_backoff={},retry_statuses={:?}>",
+            self.max_attempts, self.initial_backoff, self.max_backoff, self.retry_statuses
+        ))
+    }
+}
+
+impl RetryPolicy {
+    /// The exponential backoff before the attempt after `attempt` (starting at 1)
+    fn backoff(&self, attempt: u32) -> f64 {
+        let backoff =
+            (self.initial_backoff * self.multiplier.powi(attempt as i32 - 1)).min(self.max_backoff);
+        backoff * (1.0 - self.jitter * random())
+    }
+
+    /// The seconds to wait before the next attempt, None if the error is not retried
+    fn delay(&self, error: &anyhow::Error, attempt: u32) -> Option<f64> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        if let Some(api_error) = error.downcast_ref::<ApiError>() {
+            let status = api_error.status_code?;
+            if !self.retry_statuses.contains(&status) {
+                return None;
+            }
+            return match api_error.retry_after {
+                Some(retry_after) if retry_after > self.max_retry_after => None,
+                Some(retry_after) => Some(retry_after),
+                None => Some(self.backoff(attempt)),
+            };
+        }
+        let network_error = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+            .is_some_and(|e| e.is_connect() || e.is_timeout());
+        (self.retry_network_errors && network_error).then(|| self.backoff(attempt))
+    }
+}
+
+/// The policy of the current `GoldenAIClient`, else the one set by `set_retry_policy`, else the
+/// default one
+fn policy() -> RetryPolicy {
+    client::retry()
+        .or_else(|| POLICY.read().unwrap().clone())
+        .unwrap_or_default()
+}
+
+/// Replace the retry policy of every following request, None restores the default
+#[pyfunction]
+#[pyo3(signature = (policy=None))]
+pub fn set_retry_policy(policy: Option<RetryPolicy>) {
+    *POLICY.write().unwrap() = policy;
+}
+
+/// Send the request, again as long as the policy allows it, and hand back the successful
+/// response or the last error
+pub(crate) async fn send_with_retry(
+    provider: &'static str,
+    request: RequestBuilder,
+) -> Result<Response> {
+    let request = crate::client::with_headers(provider, request)?;
+    send_with_policy(provider, request, &policy()).await
+}
+
+async fn send_with_policy(
+    provider: &'static str,
+    request: RequestBuilder,
+    policy: &RetryPolicy,
+) -> Result<Response> {
+    let mut attempt = 1;
+    loop {
+        // the body is in memory for every request of the crate, so the request can be cloned
+        let this_request = request
+            .try_clone()
+            .ok_or_else(|| anyhow!("The request cannot be sent again"))?;
+        let result = match this_request.send().await {
+            Ok(response) => check_response(provider, response).await,
+            Err(e) => Err(e.into()),
+        };
+        let error = match result {
+            Ok(response) => return Ok(response),
+            Err(error) => error,
+        };
+        match policy.delay(&error, attempt) {
+            Some(delay) => tokio::time::sleep(Duration::from_secs_f64(delay)).await,
+            None => return Err(error),
+        }
+        attempt += 1;
+    }
+}
+
+/// The seconds to wait as asked for by the provider: `retry-after(-ms)`, the time until the
+/// exhausted limit of OpenAI (`x-ratelimit-reset-*`) or Anthropic (`anthropic-ratelimit-*-reset`)
+/// is reset, or the `retryDelay` of Google
+pub(crate) fn retry_after(headers: &HeaderMap, body: &Value) -> Option<f64> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(ms) = header("retry-after-ms").and_then(|ms| ms.parse::<f64>().ok()) {
+        return Some(ms / 1000.0);
+    }
+    if let Some(seconds) = header("retry-after").and_then(|seconds| seconds.parse().ok()) {
+        return Some(seconds);
+    }
+
+    let limits = ["requests", "tokens", "input-tokens", "output-tokens"];
+    let openai = limits.iter().filter_map(|limit| {
+        let reset = header(&format!("x-ratelimit-reset-{}", limit))?;
+        let remaining = header(&format!("x-ratelimit-remaining-{}", limit));
+        Some((remaining, parse_duration(reset)?))
+    });
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs_f64();
+    let anthropic = limits.iter().filter_map(|limit| {
+        let reset = header(&format!("anthropic-ratelimit-{}-reset", limit))?;
+        let remaining = header(&format!("anthropic-ratelimit-{}-remaining", limit));
+        Some((remaining, (parse_timestamp(reset)? - now).max(0.0)))
+    });
+    // only the limits that are used up, the headers are sent with every response
+    openai
+        .chain(anthropic)
+        .filter(|(remaining, _)| *remaining == Some("0"))
+        .map(|(_, reset)| reset)
+        .reduce(f64::max)
+        .or_else(|| retry_delay(body))
+}
+
+/// The `retryDelay` of the RetryInfo of Google, e.g. "30s"
+fn retry_delay(body: &Value) -> Option<f64> {
+    body["error"]["details"]
+        .as_array()?
+        .iter()
+        .find_map(|detail| detail["retryDelay"].as_str())?
+        .strip_suffix('s')?
+        .parse()
+        .ok()
+}
+
+/// A duration of OpenAI such as "1s", "6m0s", "20ms" or "1h2m3.5s" in seconds
+fn parse_duration(duration: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    let mut chars = duration.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let value: f64 = number.parse().ok()?;
+        number.clear();
+        seconds += match c {
+            'h' => value * 3600.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                value / 1000.0
+            }
+            'm' => value * 60.0,
+            's' => value,
+            _ => return None,
+        };
+    }
+    number.is_empty().then_some(seconds)
+}
+
+/// An RFC 3339 timestamp in UTC such as "2025-08-01T12:00:30Z" in seconds since the epoch
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let timestamp = timestamp.strip_suffix('Z')?;
+    let (date, time) = timestamp.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>());
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.splitn(3, ':');
+    let hours: f64 = time.next()?.parse().ok()?;
+    let minutes: f64 = time.next()?.parse().ok()?;
+    let seconds: f64 = time.next()?.parse().ok()?;
+
+    // the days since the epoch of a date of the proleptic Gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Some(days as f64 * 86400.0 + hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// A random number between 0 and 1, good enough for jitter
+fn random() -> f64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_nanos()),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[test]
+fn test_retry_after() {
+    assert_eq!(parse_duration("6m0s"), Some(360.0));
+    assert_eq!(parse_duration("1h2m3.5s"), Some(3723.5));
+    assert_eq!(parse_duration("20ms"), Some(0.02));
+    assert_eq!(parse_duration("soon"), None);
+    assert_eq!(parse_timestamp("1970-01-02T00:00:30Z"), Some(86430.0));
+    assert_eq!(parse_timestamp("2024-03-01T00:00:00Z"), Some(1709251200.0));
+
+    let mut headers = HeaderMap::new();
+    headers.insert("x-ratelimit-remaining-requests", "5".parse().unwrap());
+    headers.insert("x-ratelimit-reset-requests", "2s".parse().unwrap());
+    headers.insert("x-ratelimit-remaining-tokens", "0".parse().unwrap());
+    headers.insert("x-ratelimit-reset-tokens", "1m0s".parse().unwrap());
+    assert_eq!(retry_after(&headers, &Value::Null), Some(60.0));
+    headers.insert("retry-after", "7".parse().unwrap());
+    assert_eq!(retry_after(&headers, &Value::Null), Some(7.0));
+
+    let policy = RetryPolicy {
+        jitter: 0.0,
+        ..Default::default()
+    };
+    assert_eq!(policy.backoff(1), 0.5);
+    assert_eq!(policy.backoff(3), 2.0);
+    assert_eq!(policy.backoff(10), 30.0);
+}
+
+#[test]
+fn test_retry_mock_server() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // answers 429 (with retry-after), then 529 and finally 200
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v1/messages", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let answers = [
+            "429 Too Many Requests\r\nretry-after: 0",
+            "529 Overloaded",
+            "200 OK",
+        ];
+        for answer in answers {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            reader
+                .take(content_length)
+                .read_to_end(&mut Vec::new())
+                .unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{{}}",
+                answer
+            )
+            .unwrap();
+        }
+    });
+
+    let policy = RetryPolicy {
+        initial_backoff: 0.01,
+        ..Default::default()
+    };
+    let client = reqwest::Client::new();
+    let response = crate::runtime::runtime()
+        .block_on(send_with_policy(
+            "anthropic",
+            client.post(&url).body("{}"),
+            &policy,
+        ))
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    server.join().unwrap();
+
+    // the third attempt is not made with two attempts at most
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    drop(listener);
+    let policy = RetryPolicy {
+        max_attempts: 2,
+        initial_backoff: 0.01,
+        ..Default::default()
+    };
+    let error = crate::runtime::runtime()
+        .block_on(send_with_policy("anthropic", client.post(&url), &policy))
+        .unwrap_err();
+    assert!(
+        error
+            .chain()
+            .any(|cause| cause.downcast_ref::<reqwest::Error>().is_some())
+    );
+}
@@ -0,0 +1,268 @@
+use anyhow::Result;
+use pyo3::IntoPyObjectExt;
+use pyo3::prelude::*;
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::AbortHandle;
+
+use crate::client;
+use crate::error::to_py_err;
+use crate::http;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+static CLIENTS: OnceLock<Mutex<HashMap<String, Client>>> = OnceLock::new();
+static REGISTER_ATEXIT: Once = Once::new();
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+static ATTACHED: AtomicUsize = AtomicUsize::new(0);
+const MAX_CLIENTS: usize = 64;
+
+/// The process-wide tokio runtime shared by every call into the crate
+pub(crate) fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("goldenai")
+            .build()
+            .expect("Failed to build the tokio runtime")
+    })
+}
+
+/// The pooled HTTP client for the origin (scheme, host and port) of `url`, with the current HTTP
+/// settings of `provider`; the default headers of a `GoldenAIClient` are added per request
+///
+/// Clients are kept until there are `MAX_CLIENTS` of them, so their keep-alive connections (and
+/// TLS sessions) are reused by every following request to the same endpoint
+pub(crate) fn client(provider: &str, url: &str) -> Result<Client> {
+    let origin = Url::parse(url)?.origin().ascii_serialization();
+    let settings = http::settings(provider);
+    let key = format!("{} {:?}", origin, settings);
+    let mut clients = CLIENTS.get_or_init(Default::default).lock().unwrap();
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+
+    let builder = Client::builder()
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60));
+    let client = settings.apply(builder)?.build()?;
+    // e.g. a different `http=` with every call, the clients in use keep their connections
+    if clients.len() >= MAX_CLIENTS {
+        clients.clear();
+    }
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+/// Spawn `future` on the shared runtime and return an asyncio future of the running event loop
+/// which is resolved with its result
+pub(crate) fn future_into_py<'p, F, T>(py: Python<'p>, future: F) -> PyResult<Bound<'p, PyAny>>
+where
+    F: Future<Output = Result<T>> + Send + 'static,
+    T: for<'a> IntoPyObject<'a> + Send + 'static,
+{
+    REGISTER_ATEXIT.call_once(|| {
+        let registered = wrap_pyfunction!(shutdown, py)
+            .and_then(|shutdown| py.import("atexit")?.call_method1("register", (shutdown,)));
+        if let Err(e) = registered {
+            e.write_unraisable(py, None);
+        }
+    });
+
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    let py_future = event_loop.call_method0("create_future")?;
+
+    // the settings and client of the call, the future runs on a thread of the runtime
+    let future = client::scope(client::current(), http::scope(http::scoped(), future));
+    let loop_ref = event_loop.unbind();
+    let future_ref = py_future.clone().unbind();
+    let handle = runtime().spawn(async move {
+        let result = future.await;
+        attach_unless_shutdown(move |py| {
+            let result = match result {
+                Ok(value) => value.into_py_any(py),
+                Err(e) => Err(to_py_err(e)),
+            };
+            // asyncio futures are not thread-safe, resolve it on the thread of its event loop
+            let callback = SetFutureResult {
+                result: Mutex::new(Some(result)),
+            };
+            if let Err(e) = loop_ref
+                .bind(py)
+                .call_method1("call_soon_threadsafe", (callback, future_ref))
+            {
+                // the event loop is already closed, nobody is waiting for the result anymore
+                e.write_unraisable(py, None);
+            }
+        });
+    });
+
+    py_future.call_method1(
+        "add_done_callback",
+        (AbortOnCancel {
+            handle: handle.abort_handle(),
+        },),
+    )?;
+    Ok(py_future)
+}
+
+/// Attach to the interpreter from a runtime thread, unless the interpreter is shutting down
+///
+/// A runtime thread that is still attached (or waiting for the GIL again, e.g. inside
+/// `call_soon_threadsafe`) while the interpreter finalizes brings down the whole process;
+/// None if `f` was not run
+pub(crate) fn attach_unless_shutdown<F, R>(f: F) -> Option<R>
+where
+    F: for<'p> FnOnce(Python<'p>) -> R,
+{
+    ATTACHED.fetch_add(1, Ordering::SeqCst);
+    let result = match SHUTDOWN.load(Ordering::SeqCst) {
+        true => None,
+        false => Some(Python::attach(f)),
+    };
+    ATTACHED.fetch_sub(1, Ordering::SeqCst);
+    result
+}
+
+/// Registered with `atexit`, waits (for a bounded time) until no runtime thread is attached
+#[pyfunction]
+fn shutdown(py: Python<'_>) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+    py.detach(|| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while ATTACHED.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    });
+}
+
+#[pyclass]
+struct SetFutureResult {
+    result: Mutex<Option<PyResult<Py<PyAny>>>>,
+}
+
+#[pymethods]
+impl SetFutureResult {
+    fn __call__(&self, future: Bound<'_, PyAny>) -> PyResult<()> {
+        // a cancelled future must not be resolved again
+        if future.call_method0("done")?.is_truthy()? {
+            return Ok(());
+        }
+        match self.result.lock().unwrap().take() {
+            Some(Ok(value)) => future.call_method1("set_result", (value,))?,
+            Some(Err(e)) => future.call_method1("set_exception", (e.into_value(future.py()),))?,
+            None => return Ok(()),
+        };
+        Ok(())
+    }
+}
+
+#[pyclass]
+struct AbortOnCancel {
+    handle: AbortHandle,
+}
+
+#[pymethods]
+impl AbortOnCancel {
+    fn __call__(&self, future: Bound<'_, PyAny>) -> PyResult<()> {
+        if future.call_method0("cancelled")?.is_truthy()? {
+            self.handle.abort();
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_client_pool() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::sync::Arc;
+
+    // a server which answers every request of a connection and counts the connections
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let origin = format!("http://{}", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { break };
+            accepted.fetch_add(1, Ordering::SeqCst);
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                loop {
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if let Some(value) =
+                            line.to_ascii_lowercase().strip_prefix("content-length:")
+                        {
+                            length = value.trim().parse().unwrap();
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    reader.read_exact(&mut vec![0; length]).unwrap();
+                    let body = r#"{"id":"c1","choices":[{"message":{"role":"assistant","content":"pong"},"finish_reason":"stop"}],"usage":{"prompt_tokens":3,"completion_tokens":1}}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    stream.write_all(response.as_bytes()).unwrap();
+                }
+            });
+        }
+    });
+
+    let request = crate::request::ProviderRequest::OpenAICompat(
+        crate::openai::structs::OpenAICompatRequest::new(
+            &format!("{}/v1", origin),
+            "local",
+            vec![],
+            Some("ping"),
+            None,
+            "Authorization",
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap(),
+    );
+    // both requests take the pooled client of the origin and its keep-alive connection
+    for _ in 0..2 {
+        let response = runtime().block_on(request.clone().request(false)).unwrap();
+        assert_eq!(response.to_string(), "pong");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    let pooled = |origin: &str| {
+        CLIENTS
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(&format!("{} ", origin)))
+            .count()
+    };
+    assert_eq!(pooled(&origin), 1);
+
+    // other HTTP settings take a client of their own
+    let settings = http::HttpSettings {
+        timeout: Some(5.0),
+        ..Default::default()
+    };
+    http::sync_scope(Some(settings), || {
+        client("openai_compat", &format!("{}/v1/chat/completions", origin)).unwrap()
+    });
+    assert_eq!(pooled(&origin), 2);
+    assert!(std::ptr::eq(runtime(), runtime()));
+}
@@ -0,0 +1,350 @@
+use anyhow::{Result, anyhow};
+use pyo3::prelude::*;
+use pythonize::{depythonize, pythonize};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::anthropic::structs::ResponseAnthropic;
+use crate::error::{InvalidRequestError, OutputValidationError, new_err, to_py_err};
+use crate::message::{Content, ContentTypeInner, Message, TextContent};
+use crate::request::ProviderRequest;
+use crate::response::{LLMResponse, Usage};
+use crate::tools::Tool;
+
+/// A JSON value, handed to Python as the equivalent dict, list, str, number, bool or None
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Json(pub Value);
+
+impl<'py> IntoPyObject<'py> for &Json {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> PyResult<Self::Output> {
+        Ok(pythonize(py, &self.0)?)
+    }
+}
+
+impl<'py> FromPyObject<'py> for Json {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        Ok(Json(depythonize(ob)?))
+    }
+}
+
+/// The JSON Schema the output of the model must follow
+#[derive(Clone, Debug)]
+pub(crate) struct OutputSchema {
+    pub(crate) name: String,
+    pub(crate) schema: Value,
+}
+
+impl OutputSchema {
+    /// Accept either a JSON Schema as dict or a pydantic model (anything with `model_json_schema`)
+    pub(crate) fn from_py(schema: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let (name, schema) = if schema.hasattr("model_json_schema")? {
+            let name = schema.getattr("__name__")?.extract::<String>()?;
+            (
+                Some(name),
+                depythonize::<Value>(&schema.call_method0("model_json_schema")?)?,
+            )
+        } else {
+            (None, depythonize::<Value>(schema)?)
+        };
+
+        if schema["type"] != "object" {
+            return Err(new_err::<InvalidRequestError>(
+                "The schema of a structured output must describe a JSON object",
+            ));
+        }
+        jsonschema::meta::validate(&schema)
+            .map_err(|e| new_err::<InvalidRequestError>(format!("Invalid JSON Schema: {}", e)))?;
+
+        let name = name
+            .or_else(|| schema["title"].as_str().map(|s| s.to_string()))
+            .unwrap_or("response".to_string());
+        Ok(Self {
+            // OpenAI only accepts [a-zA-Z0-9_-] up to 64 characters
+            name: name
+                .chars()
+                .map(|c| match c.is_ascii_alphanumeric() || c == '-' {
+                    true => c,
+                    false => '_',
+                })
+                .take(64)
+                .collect(),
+            schema,
+        })
+    }
+
+    /// `text` of the OpenAI Responses API
+    pub(crate) fn to_openai(&self) -> Value {
+        json!({
+            "format": {
+                "type": "json_schema",
+                "name": self.name,
+                "schema": strict_schema(self.schema.clone()),
+                "strict": true,
+            }
+        })
+    }
+
+    /// `response_format` of the Chat Completions API (Mistral and OpenAI compatible servers)
+    pub(crate) fn to_chat_completions(&self) -> Value {
+        json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": self.name,
+                "schema": strict_schema(self.schema.clone()),
+                "strict": true,
+            }
+        })
+    }
+
+    /// Anthropic has no JSON mode, the output is the input of a tool the model is forced to call
+    pub(crate) fn to_anthropic(&self) -> Tool {
+        Tool {
+            name: self.name.clone(),
+            description: Some("Respond with the structured output".to_string()),
+            parameters: self.schema.clone(),
+        }
+    }
+
+    /// `format` of the Ollama chat and generate API
+    pub(crate) fn to_ollama(&self) -> Value {
+        self.schema.clone()
+    }
+
+    /// `responseJsonSchema` of the Gemini generation config
+    pub(crate) fn to_gemini(&self) -> Value {
+        self.schema.clone()
+    }
+
+    /// Every violation of the schema, empty if `value` is valid
+    pub(crate) fn validate(&self, value: &Value) -> Vec<String> {
+        match jsonschema::validator_for(&self.schema) {
+            Ok(validator) => validator
+                .iter_errors(value)
+                .map(|e| match e.instance_path().as_str() {
+                    "" => e.to_string(),
+                    path => format!("{} at {}", e, path),
+                })
+                .collect(),
+            Err(e) => vec![format!("Invalid JSON Schema: {}", e)],
+        }
+    }
+
+    /// Decode the structured output of `response` and store it as `parsed`
+    pub(crate) fn parse(&self, response: &mut LLMResponse) -> Result<()> {
+        let value = match response
+            .tool_calls
+            .iter()
+            .position(|call| call.name == self.name)
+        {
+            // Anthropic, the forced tool call is turned into a text answer, so the response
+            // can be added to the conversation without a tool result
+            Some(index) => {
+                let value = response.tool_calls.remove(index).arguments;
+                response.content = Some(vec![ResponseAnthropic {
+                    content_type: "text".to_string(),
+                    text: value.to_string(),
+                }]);
+                value
+            }
+            None => serde_json::from_str(&response.to_string())
+                .map_err(|e| anyhow!("The structured output is not valid JSON: {}", e))?,
+        };
+
+        let errors = self.validate(&value);
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "The structured output does not match the schema: {}",
+                errors.join("; ")
+            ));
+        }
+        response.parsed = Some(Json(value));
+        Ok(())
+    }
+}
+
+/// The strict mode of OpenAI requires every property to be required and no additional
+/// properties, optional fields are expected to be nullable instead (like pydantic does)
+fn strict_schema(mut schema: Value) -> Value {
+    let Some(object) = schema.as_object_mut() else {
+        return schema;
+    };
+    if let Some(properties) = object.get("properties").and_then(Value::as_object) {
+        let required: Vec<Value> = properties.keys().map(|key| json!(key)).collect();
+        object.insert("required".to_string(), Value::Array(required));
+        object
+            .entry("additionalProperties")
+            .or_insert(Value::Bool(false));
+    }
+    // `default` is not supported in strict mode
+    object.remove("default");
+
+    for key in ["properties", "$defs", "definitions"] {
+        if let Some(Value::Object(schemas)) = object.get_mut(key) {
+            for (_, value) in schemas.iter_mut() {
+                *value = strict_schema(value.take());
+            }
+        }
+    }
+    for key in ["anyOf", "allOf", "oneOf"] {
+        if let Some(Value::Array(schemas)) = object.get_mut(key) {
+            for value in schemas.iter_mut() {
+                *value = strict_schema(value.take());
+            }
+        }
+    }
+    if let Some(items) = object.get_mut("items") {
+        *items = strict_schema(items.take());
+    }
+    schema
+}
+
+/// `send` with a structured output
+///
+/// If the output is not valid, the model is asked again (up to `max_reasks` times) with the
+/// validation errors, the usage of the returned response is summed over all attempts
+pub(crate) fn send_structured(
+    py: Python<'_>,
+    request_body: &Bound<'_, PyAny>,
+    schema: &Bound<'_, PyAny>,
+    max_reasks: u32,
+) -> PyResult<LLMResponse> {
+    let schema = OutputSchema::from_py(schema)?;
+    let mut request = ProviderRequest::extract(request_body)?;
+    match &mut request {
+        // the output tool is forced, which Anthropic rejects together with extended thinking
+        ProviderRequest::Anthropic(req) if req.thinking_budget.is_some() => {
+            return Err(new_err::<InvalidRequestError>(
+                "Structured output is not supported with extended thinking (thinking_budget) on Anthropic",
+            ));
+        }
+        ProviderRequest::Anthropic(req) => {
+            req.tools.push(schema.to_anthropic());
+            req.tool_choice = Some(schema.name.clone());
+        }
+        ProviderRequest::OpenAI(req) => req.text = Some(Json(schema.to_openai())),
+        ProviderRequest::Ollama(req) => req.format = Some(Json(schema.to_ollama())),
+        ProviderRequest::Gemini(req) => req.format = Some(Json(schema.to_gemini())),
+        ProviderRequest::Mistral(req) => req.format = Some(Json(schema.to_chat_completions())),
+        ProviderRequest::OpenAICompat(req) => req.format = Some(Json(schema.to_chat_completions())),
+    }
+
+    let mut usage = Usage::default();
+    let mut attempt = 1;
+    loop {
+        py.check_signals()?;
+        // the generate mode of Ollama carries the re-asks forward with the `context` of the response
+        let mut response = py.detach(|| request.send(false)).map_err(to_py_err)?;
+        usage += response.usage.clone();
+
+        match schema.parse(&mut response) {
+            Ok(()) => {
+                response.usage = usage;
+                response.attempts = Some(attempt);
+                return Ok(response);
+            }
+            Err(e) if attempt <= max_reasks => {
+                request.add_response(response)?;
+                request.add_message(Message {
+                    role: "user".to_string(),
+                    content: vec![Content {
+                        ctx: ContentTypeInner::Text(TextContent {
+                            content_type: "text".to_string(),
+                            text: format!(
+                                "{}\n\nPlease answer again, only with JSON that follows the schema.",
+                                e
+                            ),
+                        }),
+                        cache_control: None,
+                    }],
+                });
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(new_err::<OutputValidationError>(format!(
+                    "{} (after {} attempts)",
+                    e, attempt
+                )));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_strict_schema() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "name": {"type": "string"},
+            "tags": {"type": "array", "items": {
+                "type": "object",
+                "properties": {"label": {"anyOf": [{"type": "string"}, {"type": "null"}], "default": null}},
+            }},
+        },
+        "required": ["name"],
+    });
+    let strict = strict_schema(schema);
+    assert_eq!(strict["required"], json!(["name", "tags"]));
+    assert_eq!(strict["additionalProperties"], false);
+    let item = &strict["properties"]["tags"]["items"];
+    assert_eq!(item["required"], json!(["label"]));
+    assert!(item["properties"]["label"].get("default").is_none());
+}
+
+#[test]
+fn test_structured_output_with_thinking() {
+    use crate::SupportedModels;
+    use crate::anthropic::structs::AnthropicRequest;
+
+    Python::initialize();
+    Python::attach(|py| {
+        let request = AnthropicRequest {
+            model: SupportedModels::Custom("claude-sonnet-4-5".to_string()),
+            system: None,
+            max_tokens: 4096,
+            messages: vec![],
+            tools: vec![],
+            tool_choice: None,
+            stream: None,
+            generation_config: None,
+            thinking_budget: Some(2048),
+            prompt_cache_control: None,
+        };
+        let request = Bound::new(py, request).unwrap().into_any();
+        let schema = pythonize(py, &json!({"type": "object", "properties": {}})).unwrap();
+        // rejected before anything is sent
+        let error = send_structured(py, &request, &schema, 0).unwrap_err();
+        assert!(error.is_instance_of::<InvalidRequestError>(py));
+    });
+}
+
+#[test]
+fn test_parse_structured_output() {
+    let schema = OutputSchema {
+        name: "answer".to_string(),
+        schema: json!({
+            "type": "object",
+            "properties": {"value": {"type": "integer"}},
+            "required": ["value"],
+        }),
+    };
+
+    let mut response = LLMResponse {
+        role: Some("assistant".to_string()),
+        content: Some(vec![ResponseAnthropic {
+            content_type: "text".to_string(),
+            text: "{\"value\": 42}".to_string(),
+        }]),
+        ..Default::default()
+    };
+    schema.parse(&mut response).unwrap();
+    assert_eq!(response.parsed, Some(Json(json!({"value": 42}))));
+
+    response.content.as_mut().unwrap()[0].text = "{\"value\": \"42\"}".to_string();
+    let error = schema.parse(&mut response).unwrap_err().to_string();
+    assert!(error.contains("at /value"), "{}", error);
+}
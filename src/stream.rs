@@ -0,0 +1,764 @@
+use anyhow::{Result, anyhow};
+use pyo3::prelude::*;
+use reqwest::Response;
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::anthropic::structs::ResponseAnthropic;
+use crate::error::{ApiError, to_py_err};
+use crate::gemini::structs::GeminiResponse;
+use crate::message::ThinkingContent;
+use crate::ollama::structs::{OllamaChatMessage, OllamaChatResponse, OllamaResponse};
+use crate::response::{LLMResponse, Usage};
+use crate::runtime::runtime;
+use crate::tools::ToolCall;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum StreamFormat {
+    OpenAI,          // server-sent events of the Responses API
+    Anthropic,       // server-sent events of the Messages API
+    Ollama,          // newline-delimited JSON of /api/generate
+    OllamaChat,      // newline-delimited JSON of /api/chat
+    Gemini,          // server-sent events of streamGenerateContent
+    ChatCompletions, // server-sent events of /chat/completions (Mistral and OpenAI compatible)
+}
+
+impl StreamFormat {
+    /// The provider of an error event, see ApiError
+    fn provider(&self) -> &'static str {
+        match self {
+            StreamFormat::OpenAI => "openai",
+            StreamFormat::Anthropic => "anthropic",
+            StreamFormat::Ollama | StreamFormat::OllamaChat => "ollama",
+            StreamFormat::Gemini => "gemini",
+            StreamFormat::ChatCompletions => "openai_compat", // Mistral as well
+        }
+    }
+
+    fn error(&self, body: &Value) -> anyhow::Error {
+        ApiError::from_body(self.provider(), body.clone()).into()
+    }
+}
+
+#[derive(Clone, Debug)]
+#[pyclass(dict, get_all, frozen)]
+pub struct StreamEvent {
+    pub event_type: String, // "text_delta", "thinking_delta", "tool_call", "usage" or "stop"
+    pub text: Option<String>,
+    pub tool_call: Option<ToolCall>,
+    pub usage: Option<Usage>,
+    pub stop_reason: Option<String>,
+}
+
+impl StreamEvent {
+    fn text_delta(text: &str) -> Self {
+        Self {
+            event_type: "text_delta".to_string(),
+            text: Some(text.to_string()),
+            tool_call: None,
+            usage: None,
+            stop_reason: None,
+        }
+    }
+
+    fn thinking_delta(text: &str) -> Self {
+        Self {
+            event_type: "thinking_delta".to_string(),
+            text: Some(text.to_string()),
+            tool_call: None,
+            usage: None,
+            stop_reason: None,
+        }
+    }
+
+    fn tool_call(tool_call: &ToolCall) -> Self {
+        Self {
+            event_type: "tool_call".to_string(),
+            text: None,
+            tool_call: Some(tool_call.clone()),
+            usage: None,
+            stop_reason: None,
+        }
+    }
+
+    fn usage(usage: &Usage) -> Self {
+        Self {
+            event_type: "usage".to_string(),
+            text: None,
+            tool_call: None,
+            usage: Some(usage.clone()),
+            stop_reason: None,
+        }
+    }
+
+    fn stop(stop_reason: &str) -> Self {
+        Self {
+            event_type: "stop".to_string(),
+            text: None,
+            tool_call: None,
+            usage: None,
+            stop_reason: Some(stop_reason.to_string()),
+        }
+    }
+}
+
+#[pymethods]
+impl StreamEvent {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "StreamEvent<event_type={:?},text={:?},tool_call={:?},usage={:?},stop_reason={:?}>",
+            self.event_type, self.text, self.tool_call, self.usage, self.stop_reason
+        ))
+    }
+}
+
+/// Turns raw stream chunks of any provider into `StreamEvent`s and aggregates the final response
+pub(crate) struct StreamParser {
+    format: StreamFormat,
+    buffer: Vec<u8>,
+    events: VecDeque<StreamEvent>,
+    text: String,
+    tool_calls: Vec<ToolCall>,
+    thinking: Vec<ThinkingContent>, // Anthropic, completed by the deltas
+    tool_input: Option<String>,     // partial JSON of the Anthropic tool_use block being streamed
+    tool_arguments: Vec<String>,    // partial JSON of the Chat Completions tool calls, by index
+    usage: Usage,
+    stop_reason: Option<String>,
+    response: Option<LLMResponse>,
+}
+
+impl StreamParser {
+    pub(crate) fn new(format: StreamFormat) -> Self {
+        Self {
+            format,
+            buffer: Vec::new(),
+            events: VecDeque::new(),
+            text: String::new(),
+            tool_calls: Vec::new(),
+            thinking: Vec::new(),
+            tool_input: None,
+            tool_arguments: Vec::new(),
+            usage: Usage::default(),
+            stop_reason: None,
+            response: None,
+        }
+    }
+
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        self.buffer.extend_from_slice(chunk);
+        // chunks may end in the middle of a line (or a multibyte character), keep the rest for later
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            self.parse_line(String::from_utf8_lossy(&line).trim())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn next_event(&mut self) -> Option<StreamEvent> {
+        self.events.pop_front()
+    }
+
+    /// Flush the last unterminated line and build the aggregated response
+    pub(crate) fn finish(&mut self) -> Result<LLMResponse> {
+        let rest = std::mem::take(&mut self.buffer);
+        self.parse_line(String::from_utf8_lossy(&rest).trim())?;
+
+        let mut response = self
+            .response
+            .take()
+            .ok_or_else(|| anyhow!("Stream ended before the response was completed"))?;
+
+        if matches!(
+            self.format,
+            StreamFormat::Anthropic | StreamFormat::Gemini | StreamFormat::ChatCompletions
+        ) {
+            // message_start (or every chunk of Gemini and Chat Completions) only carries a part
+            // of the content
+            response.content = Some(vec![ResponseAnthropic {
+                content_type: "text".to_string(),
+                text: self.text.clone(),
+            }]);
+            response.stop_reason = self.stop_reason.clone();
+            response.usage = self.usage.clone();
+        }
+        if self.format == StreamFormat::Anthropic {
+            response.thinking = self.thinking.clone();
+        }
+        if self.format != StreamFormat::OpenAI {
+            response.tool_calls = self.tool_calls.clone();
+        }
+
+        Ok(response)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let data = match self.format {
+            StreamFormat::OpenAI
+            | StreamFormat::Anthropic
+            | StreamFormat::Gemini
+            | StreamFormat::ChatCompletions => {
+                match line.strip_prefix("data:") {
+                    Some(data) => data.trim(),
+                    // "event:" lines repeat the type that is also part of the data
+                    None => return Ok(()),
+                }
+            }
+            StreamFormat::Ollama | StreamFormat::OllamaChat => line,
+        };
+        if data == "[DONE]" {
+            return Ok(());
+        }
+
+        let value: Value = serde_json::from_str(data)?;
+        match self.format {
+            StreamFormat::OpenAI => self.parse_openai(value),
+            StreamFormat::Anthropic => self.parse_anthropic(value),
+            StreamFormat::Ollama | StreamFormat::OllamaChat => self.parse_ollama(value),
+            StreamFormat::Gemini => self.parse_gemini(value),
+            StreamFormat::ChatCompletions => self.parse_chat_completions(value),
+        }
+    }
+
+    fn parse_openai(&mut self, value: Value) -> Result<()> {
+        match value["type"].as_str().unwrap_or_default() {
+            "response.output_text.delta" => {
+                let delta = value["delta"].as_str().unwrap_or_default();
+                self.text.push_str(delta);
+                self.events.push_back(StreamEvent::text_delta(delta));
+            }
+            "response.completed" | "response.incomplete" => {
+                let stop_reason = match value["response"]["incomplete_details"]["reason"].as_str() {
+                    Some(reason) => reason.to_string(),
+                    None => value["response"]["status"]
+                        .as_str()
+                        .unwrap_or("completed")
+                        .to_string(),
+                };
+                let response = LLMResponse::from_json(value["response"].clone())?;
+                self.usage = response.usage.clone();
+                for tool_call in &response.tool_calls {
+                    self.events.push_back(StreamEvent::tool_call(tool_call));
+                }
+                self.events.push_back(StreamEvent::usage(&self.usage));
+                self.events.push_back(StreamEvent::stop(&stop_reason));
+                self.response = Some(response);
+            }
+            "response.failed" => {
+                return Err(self.format.error(&value["response"]["error"]));
+            }
+            "error" => {
+                return Err(self.format.error(&value));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_anthropic(&mut self, value: Value) -> Result<()> {
+        match value["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                let response = LLMResponse::from_json(value["message"].clone())?;
+                self.usage = response.usage.clone();
+                self.events.push_back(StreamEvent::usage(&self.usage));
+                self.response = Some(response);
+            }
+            "content_block_start" if value["content_block"]["type"] == "tool_use" => {
+                // the input is streamed as partial JSON, the call is complete with content_block_stop
+                if let Some(tool_call) = ToolCall::from_anthropic(&value["content_block"]) {
+                    self.tool_calls.push(tool_call);
+                    self.tool_input = Some(String::new());
+                }
+            }
+            "content_block_start" => {
+                if let Some(thinking) = ThinkingContent::from_anthropic(&value["content_block"]) {
+                    self.thinking.push(thinking);
+                }
+            }
+            "content_block_delta" if value["delta"]["type"] == "thinking_delta" => {
+                let delta = value["delta"]["thinking"].as_str().unwrap_or_default();
+                if let Some(thinking) = self.thinking.last_mut() {
+                    thinking.thinking.get_or_insert_default().push_str(delta);
+                }
+                self.events.push_back(StreamEvent::thinking_delta(delta));
+            }
+            "content_block_delta" if value["delta"]["type"] == "signature_delta" => {
+                if let Some(thinking) = self.thinking.last_mut() {
+                    thinking.signature = value["delta"]["signature"].as_str().map(str::to_string);
+                }
+            }
+            "content_block_delta" if value["delta"]["type"] == "text_delta" => {
+                let delta = value["delta"]["text"].as_str().unwrap_or_default();
+                self.text.push_str(delta);
+                self.events.push_back(StreamEvent::text_delta(delta));
+            }
+            "content_block_delta" if value["delta"]["type"] == "input_json_delta" => {
+                if let Some(tool_input) = self.tool_input.as_mut() {
+                    tool_input
+                        .push_str(value["delta"]["partial_json"].as_str().unwrap_or_default());
+                }
+            }
+            "content_block_stop" => {
+                if let (Some(input), Some(tool_call)) =
+                    (self.tool_input.take(), self.tool_calls.last_mut())
+                {
+                    // a tool without arguments streams no input at all
+                    if !input.is_empty() {
+                        tool_call.arguments = serde_json::from_str(&input)?;
+                    }
+                    self.events.push_back(StreamEvent::tool_call(tool_call));
+                }
+            }
+            "message_delta" => {
+                // usage in message_delta is cumulative
+                let usage = &value["usage"];
+                if let Some(tokens) = usage["cache_creation_input_tokens"].as_u64() {
+                    self.usage.cache_creation_input_tokens = tokens as u32;
+                }
+                if let Some(tokens) = usage["cache_read_input_tokens"].as_u64() {
+                    self.usage.cache_read_input_tokens = tokens as u32;
+                }
+                if let Some(input_tokens) = usage["input_tokens"].as_u64() {
+                    // the cached tokens are counted as input, as in Usage::from
+                    self.usage.input_tokens = input_tokens as u32
+                        + self.usage.cache_creation_input_tokens
+                        + self.usage.cache_read_input_tokens;
+                }
+                if let Some(output_tokens) = value["usage"]["output_tokens"].as_u64() {
+                    self.usage.output_tokens = output_tokens as u32;
+                    self.events.push_back(StreamEvent::usage(&self.usage));
+                }
+                if let Some(stop_reason) = value["delta"]["stop_reason"].as_str() {
+                    self.stop_reason = Some(stop_reason.to_string());
+                    self.events.push_back(StreamEvent::stop(stop_reason));
+                }
+            }
+            "error" => {
+                return Err(self.format.error(&value["error"]));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_ollama(&mut self, value: Value) -> Result<()> {
+        if let Some(error) = value.get("error") {
+            return Err(self.format.error(error));
+        }
+
+        if value["done"] != true {
+            // tool calls arrive complete within one chunk of chat mode
+            if let Ok(message) =
+                serde_json::from_value::<OllamaChatMessage>(value["message"].clone())
+            {
+                for tool_call in message.to_tool_calls() {
+                    self.events.push_back(StreamEvent::tool_call(&tool_call));
+                    self.tool_calls.push(tool_call);
+                }
+            }
+            let delta = match self.format {
+                StreamFormat::OllamaChat => value["message"]["content"].as_str(),
+                _ => value["response"].as_str(),
+            }
+            .unwrap_or_default();
+            self.text.push_str(delta);
+            self.events.push_back(StreamEvent::text_delta(delta));
+            return Ok(());
+        }
+
+        // the final line carries the statistics, but no more content
+        let response = match self.format {
+            StreamFormat::OllamaChat => {
+                let mut response: OllamaChatResponse = serde_json::from_value(value)?;
+                response.message.content = self.text.clone();
+                response.to_llm_response()
+            }
+            _ => {
+                let mut response: OllamaResponse = serde_json::from_value(value)?;
+                response.response = self.text.clone();
+                response.to_llm_response()
+            }
+        };
+        self.usage = response.usage.clone();
+        self.events.push_back(StreamEvent::usage(&self.usage));
+        if let Some(stop_reason) = &response.stop_reason {
+            self.events.push_back(StreamEvent::stop(stop_reason));
+        }
+        self.response = Some(response);
+        Ok(())
+    }
+
+    fn parse_gemini(&mut self, value: Value) -> Result<()> {
+        if let Some(error) = value.get("error") {
+            return Err(self.format.error(error));
+        }
+
+        // every chunk is a complete response with the new parts only
+        let chunk: GeminiResponse = serde_json::from_value(value)?;
+        if let Some(candidate) = chunk.candidates.first() {
+            let delta = candidate.content.text();
+            if !delta.is_empty() {
+                self.text.push_str(&delta);
+                self.events.push_back(StreamEvent::text_delta(&delta));
+            }
+            for tool_call in candidate.content.to_tool_calls(self.tool_calls.len()) {
+                self.events.push_back(StreamEvent::tool_call(&tool_call));
+                self.tool_calls.push(tool_call);
+            }
+        }
+
+        let response = chunk.to_llm_response();
+        // the usage metadata is cumulative
+        self.usage = response.usage.clone();
+        if let Some(stop_reason) = &response.stop_reason {
+            self.stop_reason = Some(stop_reason.clone());
+            self.events.push_back(StreamEvent::usage(&self.usage));
+            self.events.push_back(StreamEvent::stop(stop_reason));
+        }
+        self.response = Some(response);
+        Ok(())
+    }
+
+    fn parse_chat_completions(&mut self, value: Value) -> Result<()> {
+        if let Some(error) = value.get("error") {
+            return Err(self.format.error(error));
+        }
+
+        if self.response.is_none() {
+            self.response = Some(LLMResponse {
+                id: value["id"].as_str().unwrap_or_default().to_string(),
+                model: serde_json::from_value(value["model"].clone())?,
+                response_type: "chat.completion".to_string(),
+                role: Some("assistant".to_string()),
+                ..Default::default()
+            });
+        }
+
+        let choice = &value["choices"][0];
+        if let Some(delta) = choice["delta"]["content"].as_str()
+            && !delta.is_empty()
+        {
+            self.text.push_str(delta);
+            self.events.push_back(StreamEvent::text_delta(delta));
+        }
+        // the arguments of a tool call are streamed as partial JSON, the calls are complete
+        // with the finish reason
+        for call in choice["delta"]["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let index = call["index"]
+                .as_u64()
+                .map_or(self.tool_calls.len(), |index| index as usize);
+            // the indices can arrive out of order or with gaps
+            if index >= self.tool_calls.len() {
+                self.tool_calls.resize_with(index + 1, || ToolCall {
+                    id: String::new(),
+                    name: String::new(),
+                    arguments: json!({}),
+                });
+                self.tool_arguments.resize_with(index + 1, String::new);
+            }
+            if let Some(id) = call["id"].as_str() {
+                self.tool_calls[index].id = id.to_string();
+            }
+            if let Some(name) = call["function"]["name"].as_str() {
+                self.tool_calls[index].name = name.to_string();
+            }
+            self.tool_arguments[index]
+                .push_str(call["function"]["arguments"].as_str().unwrap_or_default());
+        }
+
+        if value["usage"].is_object() {
+            self.usage = serde_json::from_value(value["usage"].clone())?;
+            self.events.push_back(StreamEvent::usage(&self.usage));
+        }
+        if let Some(stop_reason) = choice["finish_reason"].as_str() {
+            for (tool_call, arguments) in self.tool_calls.iter_mut().zip(&self.tool_arguments) {
+                if !arguments.is_empty() {
+                    tool_call.arguments = serde_json::from_str(arguments)?;
+                }
+            }
+            // the gaps of the indices are no calls
+            self.tool_calls
+                .retain(|tool_call| !tool_call.name.is_empty());
+            for tool_call in &self.tool_calls {
+                self.events.push_back(StreamEvent::tool_call(tool_call));
+            }
+            self.stop_reason = Some(stop_reason.to_string());
+            self.events.push_back(StreamEvent::stop(stop_reason));
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the `StreamEvent`s of a streamed response, the aggregated
+/// `LLMResponse` is available as `response` once the iterator is exhausted
+#[pyclass]
+pub struct LLMStream {
+    inner: Mutex<StreamInner>,
+}
+
+struct StreamInner {
+    body: Option<Response>, // None once the body has been consumed
+    parser: StreamParser,
+    response: Option<LLMResponse>,
+}
+
+impl LLMStream {
+    /// Send the request on the shared runtime, the body is then read chunk by chunk while iterating
+    pub(crate) fn start<F>(py: Python<'_>, format: StreamFormat, request: F) -> Result<Self>
+    where
+        F: Future<Output = Result<Response>> + Send,
+    {
+        let body = py.detach(|| runtime().block_on(request))?;
+        Ok(Self {
+            inner: Mutex::new(StreamInner {
+                body: Some(body),
+                parser: StreamParser::new(format),
+                response: None,
+            }),
+        })
+    }
+}
+
+impl StreamInner {
+    fn next_event(&mut self) -> Result<Option<StreamEvent>> {
+        loop {
+            if let Some(event) = self.parser.next_event() {
+                return Ok(Some(event));
+            }
+            let Some(body) = self.body.as_mut() else {
+                return Ok(None);
+            };
+            match runtime().block_on(body.chunk()) {
+                Ok(Some(chunk)) => self.parser.feed(&chunk)?,
+                Ok(None) => {
+                    self.body = None;
+                    self.response = Some(self.parser.finish()?);
+                }
+                Err(e) => {
+                    self.body = None;
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl LLMStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<StreamEvent>> {
+        match py.detach(|| self.inner.lock().unwrap().next_event()) {
+            Ok(event) => Ok(event),
+            Err(e) => Err(to_py_err(e)),
+        }
+    }
+
+    #[getter]
+    fn response(&self) -> Option<LLMResponse> {
+        self.inner.lock().unwrap().response.clone()
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "LLMStream<done={:?}>",
+            self.inner.lock().unwrap().body.is_none()
+        ))
+    }
+}
+
+#[test]
+fn test_stream_parser_anthropic() {
+    let mut parser = StreamParser::new(StreamFormat::Anthropic);
+    let chunks = [
+        "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-3-5-haiku-20241022\",\"stop_reason\":null,\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+        "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hel",
+        "lo\"}}\n\nevent: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\" world\"}}\n\n",
+        "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":5}}\n\n",
+        "event: message_stop\ndata: {\"type\":\"message_stop\"}",
+    ];
+    for chunk in chunks {
+        parser.feed(chunk.as_bytes()).unwrap();
+    }
+    let response = parser.finish().unwrap();
+
+    let mut events = Vec::new();
+    while let Some(event) = parser.next_event() {
+        events.push(event.event_type);
+    }
+    assert_eq!(
+        events,
+        ["usage", "text_delta", "text_delta", "usage", "stop"]
+    );
+    assert_eq!(response.to_string(), "Hello world");
+    assert_eq!(response.usage.input_tokens, 12);
+    assert_eq!(response.usage.output_tokens, 5);
+    assert_eq!(response.stop_reason.as_deref(), Some("end_turn"));
+}
+
+#[test]
+fn test_stream_parser_ollama() {
+    let mut parser = StreamParser::new(StreamFormat::OllamaChat);
+    let chunks = [
+        "{\"model\":\"qwen2.5vl:latest\",\"created_at\":\"2025-08-01T00:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"Blue\"},\"done\":false}\n",
+        "{\"model\":\"qwen2.5vl:latest\",\"created_at\":\"2025-08-01T00:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\" sky\"},\"done\":false}\n",
+        "{\"model\":\"qwen2.5vl:latest\",\"created_at\":\"2025-08-01T00:00:01Z\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\",\"total_duration\":1500000000,\"prompt_eval_count\":26,\"eval_count\":2}\n",
+    ];
+    for chunk in chunks {
+        parser.feed(chunk.as_bytes()).unwrap();
+    }
+    let response = parser.finish().unwrap();
+
+    assert_eq!(parser.next_event().unwrap().text.as_deref(), Some("Blue"));
+    assert_eq!(parser.next_event().unwrap().text.as_deref(), Some(" sky"));
+    assert_eq!(parser.next_event().unwrap().event_type, "usage");
+    assert_eq!(
+        parser.next_event().unwrap().stop_reason.as_deref(),
+        Some("stop")
+    );
+    assert_eq!(response.to_string(), "Blue sky");
+    assert_eq!(response.usage.input_tokens, 26);
+    assert_eq!(response.usage.output_tokens, 2);
+    assert_eq!(response.usage.durations["total"], 1.5);
+}
+
+#[test]
+fn test_stream_parser_openai() {
+    let mut parser = StreamParser::new(StreamFormat::OpenAI);
+    let chunks = [
+        "event: response.output_text.delta\ndata: {\"type\":\"response.output_text.delta\",\"item_id\":\"msg_1\",\"output_index\":0,\"content_index\":0,\"delta\":\"Hi\"}\n\n",
+        "event: response.completed\ndata: {\"type\":\"response.completed\",\"response\":{\"id\":\"resp_1\",\"object\":\"response\",\"status\":\"completed\",\"model\":\"gpt-4.1-nano-2025-04-14\",\"output\":[{\"id\":\"msg_1\",\"type\":\"message\",\"status\":\"completed\",\"role\":\"assistant\",\"content\":[{\"type\":\"output_text\",\"text\":\"Hi\",\"annotations\":[]}]}],\"usage\":{\"input_tokens\":7,\"output_tokens\":1,\"total_tokens\":8}}}\n\n",
+    ];
+    for chunk in chunks {
+        parser.feed(chunk.as_bytes()).unwrap();
+    }
+    let response = parser.finish().unwrap();
+
+    assert_eq!(parser.next_event().unwrap().text.as_deref(), Some("Hi"));
+    assert_eq!(parser.next_event().unwrap().usage.unwrap().input_tokens, 7);
+    assert_eq!(
+        parser.next_event().unwrap().stop_reason.as_deref(),
+        Some("completed")
+    );
+    assert_eq!(response.to_string(), "Hi");
+    assert_eq!(response.id, "resp_1");
+}
+
+#[test]
+fn test_stream_parser_anthropic_tool_use() {
+    let mut parser = StreamParser::new(StreamFormat::Anthropic);
+    let chunks = [
+        "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_2\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-3-5-haiku-20241022\",\"stop_reason\":null,\"usage\":{\"input_tokens\":30,\"output_tokens\":1}}}\n",
+        "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"thinking\",\"thinking\":\"\",\"signature\":\"\"}}\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"The user asks for Berlin\"}}\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"signature_delta\",\"signature\":\"sig_1\"}}\n",
+        "data: {\"type\":\"content_block_stop\",\"index\":0}\n",
+        "data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"get_weather\",\"input\":{}}}\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"city\\\": \"}}\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"\\\"Berlin\\\"}\"}}\n",
+        "data: {\"type\":\"content_block_stop\",\"index\":1}\n",
+        "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":20}}\n",
+    ];
+    for chunk in chunks {
+        parser.feed(chunk.as_bytes()).unwrap();
+    }
+    let response = parser.finish().unwrap();
+
+    assert_eq!(parser.next_event().unwrap().event_type, "usage");
+    assert_eq!(parser.next_event().unwrap().event_type, "thinking_delta");
+    let tool_call = parser.next_event().unwrap().tool_call.unwrap();
+    assert_eq!(tool_call.name, "get_weather");
+    assert_eq!(tool_call.arguments["city"], "Berlin");
+    assert_eq!(response.tool_calls.len(), 1);
+    assert_eq!(response.to_string(), "");
+    assert_eq!(
+        response.thinking[0].thinking.as_deref(),
+        Some("The user asks for Berlin")
+    );
+    assert_eq!(response.thinking[0].signature.as_deref(), Some("sig_1"));
+}
+
+#[test]
+fn test_stream_parser_gemini() {
+    let mut parser = StreamParser::new(StreamFormat::Gemini);
+    let chunks = [
+        "data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"Hel\"}]}}],\"usageMetadata\":{\"promptTokenCount\":8},\"modelVersion\":\"gemini-2.5-flash\",\"responseId\":\"r1\"}\r\n\r\n",
+        "data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"lo\"},{\"functionCall\":{\"name\":\"get_weather\",\"args\":{\"city\":\"Berlin\"}}}]},\"finishReason\":\"STOP\"}],",
+        "\"usageMetadata\":{\"promptTokenCount\":8,\"candidatesTokenCount\":4},\"modelVersion\":\"gemini-2.5-flash\",\"responseId\":\"r1\"}\r\n\r\n",
+    ];
+    for chunk in chunks {
+        parser.feed(chunk.as_bytes()).unwrap();
+    }
+    let response = parser.finish().unwrap();
+
+    let mut events = Vec::new();
+    while let Some(event) = parser.next_event() {
+        events.push(event.event_type);
+    }
+    assert_eq!(
+        events,
+        ["text_delta", "text_delta", "tool_call", "usage", "stop"]
+    );
+    assert_eq!(response.to_string(), "Hello");
+    assert_eq!(response.usage.output_tokens, 4);
+    assert_eq!(response.stop_reason.as_deref(), Some("STOP"));
+    assert_eq!(response.tool_calls[0].arguments["city"], "Berlin");
+}
+
+#[test]
+fn test_stream_parser_chat_completions() {
+    let mut parser = StreamParser::new(StreamFormat::ChatCompletions);
+    let chunks = [
+        "data: {\"id\":\"c1\",\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"}}]}\n\n",
+        "data: {\"id\":\"c1\",\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\",\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"{\\\"city\\\":\"}}]}}]}\n\n",
+        "data: {\"id\":\"c1\",\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"Berlin\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}],",
+        "\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":7,\"total_tokens\":19}}\n\ndata: [DONE]\n\n",
+    ];
+    for chunk in chunks {
+        parser.feed(chunk.as_bytes()).unwrap();
+    }
+    let response = parser.finish().unwrap();
+
+    let mut events = Vec::new();
+    while let Some(event) = parser.next_event() {
+        events.push(event.event_type);
+    }
+    assert_eq!(
+        events,
+        ["text_delta", "text_delta", "usage", "tool_call", "stop"]
+    );
+    assert_eq!(response.to_string(), "Hello");
+    assert_eq!(response.usage.input_tokens, 12);
+    assert_eq!(response.stop_reason.as_deref(), Some("tool_calls"));
+    assert_eq!(response.tool_calls[0].id, "call_1");
+    assert_eq!(response.tool_calls[0].arguments["city"], "Berlin");
+}
+
+#[test]
+fn test_stream_parser_chat_completions_tool_call_indices() {
+    let mut parser = StreamParser::new(StreamFormat::ChatCompletions);
+    let chunks = [
+        "data: {\"id\":\"c2\",\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":2,\"id\":\"call_b\",\"function\":{\"name\":\"get_time\",\"arguments\":\"{}\"}}]}}]}\n\n",
+        "data: {\"id\":\"c2\",\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_a\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"{\\\"city\\\":\\\"Paris\\\"}\"}}]}}]}\n\n",
+        "data: {\"id\":\"c2\",\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"tool_calls\"}]}\n\ndata: [DONE]\n\n",
+    ];
+    for chunk in chunks {
+        parser.feed(chunk.as_bytes()).unwrap();
+    }
+    let response = parser.finish().unwrap();
+
+    assert_eq!(response.tool_calls.len(), 2);
+    assert_eq!(response.tool_calls[0].id, "call_a");
+    assert_eq!(response.tool_calls[0].arguments["city"], "Paris");
+    assert_eq!(response.tool_calls[1].name, "get_time");
+}
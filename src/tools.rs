@@ -0,0 +1,299 @@
+use pyo3::IntoPyObjectExt;
+use pyo3::prelude::*;
+use pythonize::{depythonize, pythonize};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value, json};
+
+/// A function the model may call, described provider-neutrally by a JSON schema of its arguments
+#[derive(Serialize, Clone, Debug)]
+#[pyclass(dict, subclass)]
+pub struct Tool {
+    #[pyo3(get, set)]
+    pub name: String,
+    #[pyo3(get, set)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub parameters: Value,
+}
+
+#[pymethods]
+impl Tool {
+    #[new]
+    #[pyo3(signature = (name, description=None, parameters=None))]
+    pub fn new(
+        name: &str,
+        description: Option<&str>,
+        parameters: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let parameters = match parameters {
+            Some(parameters) => depythonize::<Value>(&parameters)?,
+            // a function without arguments
+            None => json!({"type": "object", "properties": {}}),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            description: description.map(|s| s.to_string()),
+            parameters,
+        })
+    }
+
+    #[getter]
+    fn parameters(&self, py: Python) -> PyResult<Py<PyAny>> {
+        pythonize(py, &self.parameters)?.into_py_any(py)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "Tool<name={:?},description={:?}>",
+            self.name, self.description
+        ))
+    }
+}
+
+impl Tool {
+    fn with_description(&self, mut tool: Map<String, Value>) -> Value {
+        if let Some(description) = &self.description {
+            tool.insert("description".to_string(), json!(description));
+        }
+        Value::Object(tool)
+    }
+
+    pub(crate) fn to_openai(&self) -> Value {
+        let mut tool = Map::new();
+        tool.insert("type".to_string(), json!("function"));
+        tool.insert("name".to_string(), json!(self.name));
+        tool.insert("parameters".to_string(), self.parameters.clone());
+        self.with_description(tool)
+    }
+
+    pub(crate) fn to_anthropic(&self) -> Value {
+        let mut tool = Map::new();
+        tool.insert("name".to_string(), json!(self.name));
+        tool.insert("input_schema".to_string(), self.parameters.clone());
+        self.with_description(tool)
+    }
+
+    /// Ollama uses the tool schema of Chat Completions as well
+    pub(crate) fn to_chat_completions(&self) -> Value {
+        self.to_ollama()
+    }
+
+    pub(crate) fn to_ollama(&self) -> Value {
+        let mut function = Map::new();
+        function.insert("name".to_string(), json!(self.name));
+        function.insert("parameters".to_string(), self.parameters.clone());
+        json!({"type": "function", "function": self.with_description(function)})
+    }
+
+    pub(crate) fn to_gemini(&self) -> Value {
+        let mut tool = Map::new();
+        tool.insert("name".to_string(), json!(self.name));
+        // `parameters` only accepts a subset of OpenAPI, `parametersJsonSchema` any JSON Schema
+        tool.insert("parametersJsonSchema".to_string(), self.parameters.clone());
+        self.with_description(tool)
+    }
+}
+
+pub(crate) fn serialize_openai_tools<S>(tools: &[Tool], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(tools.iter().map(Tool::to_openai))
+}
+
+pub(crate) fn serialize_anthropic_tools<S>(tools: &[Tool], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(tools.iter().map(Tool::to_anthropic))
+}
+
+// tool_choice is one of "auto", "none", "required" (alias "any") or the name of a tool to force
+
+pub(crate) fn serialize_openai_tool_choice<S>(
+    tool_choice: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match tool_choice.as_deref() {
+        None => serializer.serialize_none(),
+        Some("auto") => serializer.serialize_str("auto"),
+        Some("none") => serializer.serialize_str("none"),
+        Some("required") | Some("any") => serializer.serialize_str("required"),
+        Some(name) => json!({"type": "function", "name": name}).serialize(serializer),
+    }
+}
+
+pub(crate) fn serialize_anthropic_tool_choice<S>(
+    tool_choice: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match tool_choice.as_deref() {
+        None => serializer.serialize_none(),
+        Some("auto") => json!({"type": "auto"}).serialize(serializer),
+        Some("none") => json!({"type": "none"}).serialize(serializer),
+        Some("required") | Some("any") => json!({"type": "any"}).serialize(serializer),
+        Some(name) => json!({"type": "tool", "name": name}).serialize(serializer),
+    }
+}
+
+/// `toolConfig` of Gemini, which has no tool_choice but restricts the functions that can be called
+pub(crate) fn gemini_tool_config(tool_choice: &Option<String>) -> Option<Value> {
+    let config = match tool_choice.as_deref()? {
+        "auto" => json!({"mode": "AUTO"}),
+        "none" => json!({"mode": "NONE"}),
+        "required" | "any" => json!({"mode": "ANY"}),
+        name => json!({"mode": "ANY", "allowedFunctionNames": [name]}),
+    };
+    Some(json!({"functionCallingConfig": config}))
+}
+
+/// A call of a `Tool` requested by the model
+#[derive(Clone, Debug)]
+#[pyclass(dict, frozen)]
+pub struct ToolCall {
+    #[pyo3(get)]
+    pub id: String,
+    #[pyo3(get)]
+    pub name: String,
+    pub arguments: Value,
+}
+
+#[pymethods]
+impl ToolCall {
+    #[new]
+    #[pyo3(signature = (id, name, arguments=None))]
+    pub fn new(id: &str, name: &str, arguments: Option<Bound<'_, PyAny>>) -> PyResult<Self> {
+        Ok(Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            arguments: match arguments {
+                Some(arguments) => depythonize::<Value>(&arguments)?,
+                None => json!({}),
+            },
+        })
+    }
+
+    #[getter]
+    fn arguments(&self, py: Python) -> PyResult<Py<PyAny>> {
+        pythonize(py, &self.arguments)?.into_py_any(py)
+    }
+
+    pub(crate) fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "ToolCall<id={:?},name={:?},arguments={}>",
+            self.id, self.name, self.arguments
+        ))
+    }
+}
+
+impl ToolCall {
+    /// `tool_use` content block of Anthropic
+    pub(crate) fn from_anthropic(value: &Value) -> Option<Self> {
+        if value["type"] != "tool_use" {
+            return None;
+        }
+        Some(Self {
+            id: value["id"].as_str()?.to_string(),
+            name: value["name"].as_str()?.to_string(),
+            arguments: value["input"].clone(),
+        })
+    }
+
+    /// `function_call` output item of OpenAI, the arguments are a JSON encoded string
+    pub(crate) fn from_openai(value: &Value) -> Option<Self> {
+        if value["type"] != "function_call" {
+            return None;
+        }
+        let arguments = value["arguments"].as_str().unwrap_or("{}");
+        Some(Self {
+            id: value["call_id"].as_str()?.to_string(),
+            name: value["name"].as_str()?.to_string(),
+            arguments: serde_json::from_str(arguments)
+                .unwrap_or_else(|_| Value::String(arguments.to_string())),
+        })
+    }
+
+    /// `tool_calls` item of a Chat Completions message, the arguments are a JSON encoded string
+    pub(crate) fn from_chat_completions(value: &Value) -> Option<Self> {
+        let arguments = value["function"]["arguments"].as_str().unwrap_or("{}");
+        Some(Self {
+            id: value["id"].as_str()?.to_string(),
+            name: value["function"]["name"].as_str()?.to_string(),
+            arguments: serde_json::from_str(arguments)
+                .unwrap_or_else(|_| Value::String(arguments.to_string())),
+        })
+    }
+
+    pub(crate) fn to_chat_completions(&self) -> Value {
+        json!({
+            "id": self.id,
+            "type": "function",
+            "function": {"name": self.name, "arguments": self.arguments.to_string()},
+        })
+    }
+
+    pub(crate) fn to_openai(&self) -> Value {
+        json!({
+            "type": "function_call",
+            "call_id": self.id,
+            "name": self.name,
+            "arguments": self.arguments.to_string(),
+        })
+    }
+
+    pub(crate) fn to_ollama(&self) -> Value {
+        json!({"function": {"name": self.name, "arguments": self.arguments}})
+    }
+
+    pub(crate) fn to_gemini(&self) -> Value {
+        json!({"functionCall": {"name": self.name, "args": self.arguments}})
+    }
+}
+
+impl Serialize for ToolCall {
+    // Anthropic schema, the other providers are converted explicitly
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(4))?;
+        map.serialize_entry("type", "tool_use")?;
+        map.serialize_entry("id", &self.id)?;
+        map.serialize_entry("name", &self.name)?;
+        map.serialize_entry("input", &self.arguments)?;
+        map.end()
+    }
+}
+
+#[test]
+fn test_tool_wire_formats() {
+    let tool = Tool {
+        name: "get_weather".to_string(),
+        description: Some("Current weather of a city".to_string()),
+        parameters: json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+    };
+    assert_eq!(tool.to_openai()["type"], "function");
+    assert_eq!(tool.to_anthropic()["input_schema"]["type"], "object");
+    assert_eq!(tool.to_ollama()["function"]["name"], "get_weather");
+
+    let call = ToolCall::from_openai(&json!({
+        "type": "function_call",
+        "call_id": "call_1",
+        "name": "get_weather",
+        "arguments": "{\"city\":\"Berlin\"}",
+    }))
+    .unwrap();
+    assert_eq!(call.arguments["city"], "Berlin");
+    assert_eq!(call.to_openai()["arguments"], "{\"city\":\"Berlin\"}");
+    assert_eq!(
+        serde_json::to_value(&call).unwrap(),
+        json!({"type": "tool_use", "id": "call_1", "name": "get_weather", "input": {"city": "Berlin"}})
+    );
+}